@@ -0,0 +1,156 @@
+// Finds the parts of a handler body that depend on the number of arguments (Scr_GetNumParam)
+
+use regex::Regex;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+use crate::cpp_lexer::{self, Token, TokenKind};
+
+// Code that only runs when at least min_args arguments were passed, e.g.
+//     if (Scr_GetNumParam() > 1) { ... }
+#[derive(Debug)]
+pub(crate) struct GuardedRegion {
+    pub min_args: usize,
+    pub range: Range<usize>,    // byte range in the body, condition included
+}
+
+// Loop over the remaining arguments, e.g.
+//     for (int i = 1; i < Scr_GetNumParam(); i++) { ... }
+#[derive(Debug)]
+pub(crate) struct ArgumentLoop {
+    pub first_index: usize,
+    pub counter: String,
+    pub range: Range<usize>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ArityInfo {
+    pub guarded: Vec<GuardedRegion>,
+    pub loops: Vec<ArgumentLoop>,
+}
+
+impl ArityInfo {
+    // Whether code at pos only runs once the argument at index has been passed
+    pub fn is_guarded(&self, pos: usize, index: usize) -> bool
+    {
+        self.guarded.iter().any(|g| g.range.contains(&pos) && g.min_args > index)
+    }
+}
+
+static ALIAS_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\w+)\s*=\s*Scr_GetNumParam\s*\(\s*\)").unwrap());
+
+// Scr_GetNumParam() or a variable on one side of the comparison, checked against the aliases after matching
+const COUNT: &str = r"(Scr_GetNumParam\s*\(\s*\)|\b\w+\b)";
+static COMPARE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(r"{COUNT}\s*(>=|>|==)\s*(\d+)|(\d+)\s*(<=|<|==)\s*{COUNT}")).unwrap()
+});
+static LOOP_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(r"^\s*(?:\w+\s+)?(\w+)\s*=\s*(\d+)\s*;\s*(\w+)\s*(?:<|!=)\s*{COUNT}\s*;")).unwrap()
+});
+// int flag = 0;
+static DECLARATION_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b\w+[\s\*]+(\w+)\s*=\s*([^;,]+?)\s*;").unwrap());
+
+pub(crate) fn analyze(body: &str) -> ArityInfo
+{
+    let mut info = ArityInfo::default();
+
+    // Variables holding the argument count, e.g. int numParams = Scr_GetNumParam();
+    let aliases: Vec<&str> = ALIAS_RE.captures_iter(body).map(|c| c.get(1).unwrap().as_str()).collect();
+    let is_count = |operand: &str| operand.starts_with("Scr_GetNumParam") || aliases.contains(&operand);
+
+    let tokens: Vec<Token> = cpp_lexer::tokenize(body)
+        .into_iter()
+        .filter(|t| t.kind != TokenKind::Comment)
+        .collect();
+
+    for (i, token) in tokens.iter().enumerate() {
+        if !(token.is("if") || token.is("for")) || !tokens.get(i + 1).is_some_and(|t| t.is("(")) {
+            continue;
+        }
+        let Some(close) = cpp_lexer::matching_close(&tokens, i + 1) else {
+            continue;
+        };
+        let header = &body[tokens[i + 1].end()..tokens[close].start];
+        let end = cpp_lexer::statement_end(&tokens, close + 1).map_or(body.len(), |t| tokens[t].end());
+
+        if token.is("if") {
+            // The strongest check wins: (n > 1 && n == 3) needs 3 arguments
+            let min_args = COMPARE_RE.captures_iter(header)
+                .filter_map(|c| {
+                    let (operand, op, n) = match (c.get(1), c.get(2), c.get(3)) {
+                        (Some(operand), Some(op), Some(n)) => (operand.as_str(), op.as_str(), n.as_str()),
+                        _ => (c.get(6)?.as_str(), c.get(5)?.as_str(), c.get(4)?.as_str()),
+                    };
+                    if !is_count(operand) {
+                        return None;
+                    }
+                    let n: usize = n.parse().ok()?;
+                    Some(if op == ">" || op == "<" { n + 1 } else { n })
+                })
+                .max();
+            if let Some(min_args) = min_args {
+                info.guarded.push(GuardedRegion { min_args, range: token.start..end });
+            }
+        } else if let Some(c) = LOOP_RE.captures(header) && c[1] == c[3] && is_count(&c[4]) {
+            info.loops.push(ArgumentLoop {
+                first_index: c[2].parse().unwrap_or(0),
+                counter: c[1].to_string(),
+                range: token.start..end,
+            });
+        }
+    }
+    info
+}
+
+// Initial value of a local, e.g. "0" for: int flag = 0;
+pub(crate) fn default_value(body: &str, var: &str) -> Option<String>
+{
+    DECLARATION_RE.captures_iter(body)
+        .find(|c| &c[1] == var)
+        .map(|c| c[2].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn argument_count_checks_guard_their_block()
+    {
+        let body = "char *s = Scr_GetString(0);\nif (Scr_GetNumParam() > 1) {\n    n = Scr_GetInt(1);\n}\nend();";
+        let info = analyze(body);
+        assert_eq!(info.guarded.len(), 1);
+        assert_eq!(info.guarded[0].min_args, 2);
+        let inside = body.find("Scr_GetInt").unwrap();
+        assert!(info.is_guarded(inside, 1));
+        assert!(!info.is_guarded(inside, 2));
+        assert!(!info.is_guarded(body.find("Scr_GetString").unwrap(), 0));
+        assert!(!info.is_guarded(body.find("end").unwrap(), 1));
+    }
+
+    #[test]
+    fn aliases_and_reversed_comparisons()
+    {
+        let body = "int numParams = Scr_GetNumParam();\nif (3 <= numParams) x = Scr_GetInt(2);\nif (other > 5) y = 1;";
+        let info = analyze(body);
+        assert_eq!(info.guarded.len(), 1);
+        assert_eq!(info.guarded[0].min_args, 3);
+    }
+
+    #[test]
+    fn loops_over_the_remaining_arguments()
+    {
+        let body = "for (int i = 1; i < Scr_GetNumParam(); i++) { Scr_GetString(i); }";
+        let info = analyze(body);
+        assert_eq!(info.loops.len(), 1);
+        assert_eq!(info.loops[0].first_index, 1);
+        assert_eq!(info.loops[0].counter, "i");
+    }
+
+    #[test]
+    fn default_value_of_a_local()
+    {
+        assert_eq!(default_value("int mode = 0;\nfloat scale = 1.5f;", "scale").as_deref(), Some("1.5f"));
+        assert_eq!(default_value("int mode;", "mode"), None);
+    }
+}
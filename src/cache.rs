@@ -0,0 +1,161 @@
+// Parse results of unchanged files and handlers from the previous run, keyed by content hash
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::source_parser::{ScriptFunctionDetails, SourceFile};
+
+pub const CACHE_FILE: &str = ".ScriptDocTool.cache";
+
+// Bump when the cached structures or the analysis change
+const CACHE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Default)]
+struct CacheData {
+    version: String,
+    // Fingerprint of the type tables the handlers were analyzed with
+    types: u64,
+    // Source path -> content hash and parsed file
+    files: HashMap<String, (u64, SourceFile)>,
+    // Hash of the handler with its helpers inlined -> analysis
+    handlers: HashMap<String, ScriptFunctionDetails>,
+}
+
+pub(crate) struct Cache {
+    // None when caching is disabled
+    path: Option<PathBuf>,
+    previous: CacheData,
+    // Only what this run used is written back
+    current: CacheData,
+    pub file_hits: usize,
+    pub handler_hits: usize,
+}
+
+impl Cache {
+    // An unreadable or outdated cache file is the same as none
+    pub fn load(path: Option<&Path>, types: u64) -> Cache
+    {
+        let version = format!("{}-{}", env!("CARGO_PKG_VERSION"), CACHE_VERSION);
+        let previous = path
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|text| serde_json::from_str::<CacheData>(&text).ok())
+            .filter(|data| data.version == version && data.types == types)
+            .unwrap_or_default();
+        Cache {
+            path: path.map(Path::to_path_buf),
+            previous,
+            current: CacheData { version, types, ..Default::default() },
+            file_hits: 0,
+            handler_hits: 0,
+        }
+    }
+
+    pub fn source_file(&mut self, path: &Path, hash: u64) -> Option<SourceFile>
+    {
+        let key = path.to_string_lossy().to_string();
+        let (cached_hash, source) = self.previous.files.remove(&key)?;
+        if cached_hash != hash {
+            return None;
+        }
+        self.file_hits += 1;
+        Some(source)
+    }
+
+    pub fn store_source_file(&mut self, path: &Path, hash: u64, source: &SourceFile)
+    {
+        self.current.files.insert(path.to_string_lossy().to_string(), (hash, source.clone()));
+    }
+
+    pub fn handler(&mut self, hash: u64) -> Option<ScriptFunctionDetails>
+    {
+        let details = self.previous.handlers.get(&format!("{:016x}", hash))?.clone();
+        self.handler_hits += 1;
+        Some(details)
+    }
+
+    pub fn store_handler(&mut self, hash: u64, details: &ScriptFunctionDetails)
+    {
+        self.current.handlers.insert(format!("{:016x}", hash), details.clone());
+    }
+
+    pub fn save(&self) -> Result<()>
+    {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let text = serde_json::to_string(&self.current).map_err(|e| Error::io(path)(e.into()))?;
+        fs::write(path, text).map_err(Error::io(path))
+    }
+}
+
+// FNV-1a, stable across runs and platforms unlike std's hasher
+pub(crate) fn fnv1a(parts: &[&[u8]]) -> u64
+{
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in parts {
+        for byte in part.iter() {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        // Separates the parts, so ("ab", "c") and ("a", "bc") differ
+        hash ^= 0xff;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source_parser::{analyze_handler, parse_source_file};
+    use crate::types::TypeTables;
+
+    #[test]
+    fn unchanged_content_hits_changed_content_misses()
+    {
+        let path = std::env::temp_dir().join(format!("ScriptDocTool-{}.cache", std::process::id()));
+        let source_path = Path::new("src/gsc/gsc_utils.cpp");
+        let source = parse_source_file("void gsc_utils_sqrt() { Scr_AddFloat(0); }");
+        let details = analyze_handler("Scr_AddFloat(0);", None, &TypeTables::preset("t1x").unwrap());
+
+        let mut cache = Cache::load(Some(&path), 1);
+        assert!(cache.source_file(source_path, 10).is_none());
+        cache.store_source_file(source_path, 10, &source);
+        cache.store_handler(20, &details);
+        cache.save().unwrap();
+
+        let mut cache = Cache::load(Some(&path), 1);
+        assert!(cache.source_file(source_path, 11).is_none());
+        assert!(cache.handler(21).is_none());
+        assert_eq!(cache.handler(20).map(|d| d.returns), Some(vec!["float".to_string()]));
+        assert_eq!((cache.file_hits, cache.handler_hits), (0, 1));
+
+        let mut cache = Cache::load(Some(&path), 1);
+        assert!(cache.source_file(source_path, 10).is_some_and(|s| s.functions[0].name == "gsc_utils_sqrt"));
+        assert_eq!(cache.file_hits, 1);
+
+        // Other type tables make every handler analysis stale
+        let mut cache = Cache::load(Some(&path), 2);
+        assert!(cache.handler(20).is_none());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn disabled_cache_writes_nothing()
+    {
+        let mut cache = Cache::load(None, 1);
+        cache.store_handler(1, &analyze_handler("", None, &TypeTables::preset("t1x").unwrap()));
+        cache.save().unwrap();
+        assert!(cache.handler(1).is_none());
+    }
+
+    #[test]
+    fn hashes_separate_their_parts()
+    {
+        assert_ne!(fnv1a(&[b"ab", b"c"]), fnv1a(&[b"a", b"bc"]));
+        assert_eq!(fnv1a(&[b"gsc"]), fnv1a(&[b"gsc"]));
+    }
+}
@@ -0,0 +1,292 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use toml::Spanned;
+
+use crate::cpp_lexer;
+use crate::error::{Error, Result};
+
+pub const CONFIG_FILE: &str = "ScriptDocTool.toml";
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // Codebase layout, "t1x" (default), "iw1x" or "libcod", see frontend.rs
+    pub frontend: Option<String>,
+    pub filters: FilterConfig,
+    // Doc category -> filter rules, e.g. weapons = ["handler:gsc_custom_weapons_*"]
+    pub categories: BTreeMap<String, Vec<String>>,
+    pub types: TypesConfig,
+    pub docs: DocsConfig,
+    pub paths: PathsConfig,
+    pub arrays: ArraysConfig,
+    // Where the values were read from, empty for a Config built in code
+    #[serde(skip)]
+    pub origin: ConfigOrigin,
+}
+
+// ScriptDocTool.toml and the line of each value that is only checked after loading,
+// e.g. a filter rule's regex, so errors about them can point there
+#[derive(Debug, Default)]
+pub struct ConfigOrigin {
+    pub file: Option<PathBuf>,
+    pub lines: Vec<(String, usize)>,
+}
+
+impl ConfigOrigin {
+    // Adds the file and line to a Config error about value, when value was read from the file
+    pub(crate) fn locate(&self, value: &str, error: Error) -> Error
+    {
+        match (error, self.lines.iter().find(|(v, _)| v == value)) {
+            (Error::Config { message, .. }, Some((_, line))) => Error::Config {
+                file: self.file.clone(),
+                line: Some(*line),
+                message,
+            },
+            (error, _) => error,
+        }
+    }
+}
+
+// The values ConfigOrigin keeps lines for, read again with their spans
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ValueSpans {
+    frontend: Option<Spanned<String>>,
+    filters: FilterSpans,
+    categories: BTreeMap<String, Vec<Spanned<String>>>,
+    types: TypeSpans,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct FilterSpans {
+    include: Vec<Spanned<String>>,
+    exclude: Vec<Spanned<String>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct TypeSpans {
+    preset: Option<Spanned<String>>,
+    letters: BTreeMap<Spanned<String>, toml::Value>,
+}
+
+// Overrides of where things are, the frontend decides the rest
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathsConfig {
+    pub root: Option<PathBuf>,          // server directory, relative to ScriptDocTool.toml
+    pub registry: Option<PathBuf>,      // relative to root, defaults to gsc.cpp in src_dir
+    pub src_dir: Option<PathBuf>,       // relative to root, e.g. src/gsc
+    pub docs_dir: Option<PathBuf>,      // relative to root, e.g. docs/source/pages/scripting
+}
+
+// Names of the registry arrays in gsc.cpp, the frontend's when not set
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArraysConfig {
+    pub functions: Option<String>,      // e.g. scriptFunctions
+    pub methods: Option<String>,        // e.g. scriptMethods
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct DocsConfig {
+    // Link for "Defined in", {file} and {line} are replaced,
+    // e.g. "https://github.com/<owner>/<repo>/blob/master/{file}#L{line}"
+    pub source_url: Option<String>,
+}
+
+// Additions and overrides on top of the built-in type tables, see types.rs
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct TypesConfig {
+    pub preset: Option<String>,                 // "t1x", "iw1x" or "libcod", defaults to the frontend
+    pub letters: BTreeMap<String, String>,      // stackGetParams letter -> type
+    pub accessors: BTreeMap<String, String>,    // Scr_Get<X>/stackGetParam<X> suffix -> type
+    pub returns: BTreeMap<String, String>,      // Scr_Add* function -> type
+    pub display: BTreeMap<String, String>,      // type -> name used in the docs
+}
+
+// Rules look like "[script|handler|category:]pattern", see filter.rs
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterConfig {
+    pub include: Vec<String>,
+    // None means the built-in default
+    pub exclude: Option<Vec<String>>,
+}
+
+// Skip the test builtins unless the config says otherwise
+const DEFAULT_EXCLUDE: &[&str] = &["handler:gsc_test*"];
+
+// SCRIPTDOCTOOL_<NAME> overrides the config value of the same name
+const ENV_PREFIX: &str = "SCRIPTDOCTOOL_";
+
+impl Config {
+    // Reads <root>/ScriptDocTool.toml, all defaults when there is none
+    pub fn load(root: &Path) -> Result<Config>
+    {
+        let path = root.join(CONFIG_FILE);
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let text = read_to_string(&path).map_err(Error::io(&path))?;
+        let mut config: Config = toml::from_str(&text).map_err(|e| Error::Config {
+            line: e.span().map(|span| cpp_lexer::line_at(&text, span.start)),
+            file: Some(path.clone()),
+            message: e.message().to_string(),
+        })?;
+        let spans: ValueSpans = toml::from_str(&text).unwrap_or_default();
+        let lines = spans.frontend.into_iter()
+            .chain(spans.filters.include)
+            .chain(spans.filters.exclude)
+            .chain(spans.categories.into_values().flatten())
+            .chain(spans.types.preset)
+            .chain(spans.types.letters.into_keys())
+            .map(|value| (value.get_ref().clone(), cpp_lexer::line_at(&text, value.span().start)))
+            .collect();
+        config.origin = ConfigOrigin { file: Some(path), lines };
+        Ok(config)
+    }
+
+    // The nearest ScriptDocTool.toml in dir or above it, with the directory it is in.
+    // Without one, the defaults and dir itself
+    pub fn find(dir: &Path) -> Result<(PathBuf, Config)>
+    {
+        for ancestor in dir.ancestors() {
+            if ancestor.join(CONFIG_FILE).is_file() {
+                return Ok((ancestor.to_path_buf(), Config::load(ancestor)?));
+            }
+        }
+        Ok((dir.to_path_buf(), Config::default()))
+    }
+
+    // The server directory, config_dir is where ScriptDocTool.toml is
+    pub fn root(&self, config_dir: &Path) -> PathBuf
+    {
+        match &self.paths.root {
+            Some(root) => config_dir.join(root),
+            None => config_dir.to_path_buf(),
+        }
+    }
+
+    // Applies the SCRIPTDOCTOOL_* variables among vars, e.g. SCRIPTDOCTOOL_SRC_DIR=src
+    pub fn apply_env(&mut self, vars: impl IntoIterator<Item = (String, String)>) -> Result<()>
+    {
+        let mut errors: Vec<Error> = Vec::new();
+        for (name, value) in vars {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            match key {
+                "FRONTEND" => self.frontend = Some(value),
+                "ROOT" => self.paths.root = Some(PathBuf::from(value)),
+                "REGISTRY" => self.paths.registry = Some(PathBuf::from(value)),
+                "SRC_DIR" => self.paths.src_dir = Some(PathBuf::from(value)),
+                "DOCS_DIR" => self.paths.docs_dir = Some(PathBuf::from(value)),
+                "SOURCE_URL" => self.docs.source_url = Some(value),
+                "TYPES_PRESET" => self.types.preset = Some(value),
+                _ => errors.push(Error::Config {
+                    file: None,
+                    line: None,
+                    message: format!("Unknown environment variable {}, expected {}FRONTEND, ROOT, REGISTRY, SRC_DIR, DOCS_DIR, SOURCE_URL or TYPES_PRESET", name, ENV_PREFIX),
+                }),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(Error::many(errors));
+        }
+        Ok(())
+    }
+
+    pub fn frontend_name(&self) -> &str
+    {
+        self.frontend.as_deref().unwrap_or("t1x")
+    }
+
+    pub fn exclude_rules(&self) -> Vec<String>
+    {
+        match &self.filters.exclude {
+            Some(rules) => rules.clone(),
+            None => DEFAULT_EXCLUDE.iter().map(|r| r.to_string()).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::Filters;
+    use crate::types::TypeTables;
+    use std::fs;
+
+    // A directory with only a ScriptDocTool.toml
+    fn config_dir(name: &str, text: &str) -> PathBuf
+    {
+        let dir = std::env::temp_dir().join(format!("ScriptDocTool-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(CONFIG_FILE), text).unwrap();
+        dir
+    }
+
+    #[test]
+    fn errors_about_loaded_values_point_at_their_line()
+    {
+        let dir = config_dir("locations", "[filters]\ninclude = [\"/[/\"]\n\n[types]\npreset = \"iw2x\"\n");
+        let config = Config::load(&dir).unwrap();
+        let file = Some(dir.join(CONFIG_FILE));
+
+        let error = Filters::from_config(&config).unwrap_err();
+        assert!(matches!(&error, Error::Config { file: f, line: Some(2), .. } if *f == file), "{:?}", error);
+        let error = TypeTables::from_config(&config, "t1x").unwrap_err();
+        assert!(matches!(&error, Error::Config { file: f, line: Some(5), .. } if *f == file), "{:?}", error);
+
+        // Rules that did not come from the file have no location
+        let error = Filters::new(&["/[/".to_string()], &[]).unwrap_err();
+        assert!(matches!(error, Error::Config { file: None, line: None, .. }));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn find_uses_the_nearest_config_above()
+    {
+        let dir = config_dir("find", "frontend = \"libcod\"\n[paths]\nroot = \"server\"\n");
+        let nested = dir.join("server/src");
+        fs::create_dir_all(&nested).unwrap();
+
+        let (config_dir, config) = Config::find(&nested).unwrap();
+        assert_eq!(config_dir, dir);
+        assert_eq!(config.frontend_name(), "libcod");
+        assert_eq!(config.root(&config_dir), dir.join("server"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        // Without a config anywhere above, the defaults and the directory itself
+        let (config_dir, config) = Config::find(Path::new("/")).unwrap();
+        assert_eq!(config_dir, Path::new("/"));
+        assert_eq!(config.frontend_name(), "t1x");
+    }
+
+    #[test]
+    fn env_overrides_the_config()
+    {
+        let mut config = Config { frontend: Some("libcod".to_string()), ..Default::default() };
+        let vars = [
+            ("SCRIPTDOCTOOL_FRONTEND", "iw1x"),
+            ("SCRIPTDOCTOOL_SRC_DIR", "src"),
+            ("SCRIPTDOCTOOL_TYPES_PRESET", "t1x"),
+            ("PATH", "/usr/bin"),
+        ];
+        config.apply_env(vars.map(|(name, value)| (name.to_string(), value.to_string()))).unwrap();
+        assert_eq!(config.frontend_name(), "iw1x");
+        assert_eq!(config.paths.src_dir, Some(PathBuf::from("src")));
+        assert_eq!(config.types.preset.as_deref(), Some("t1x"));
+
+        let vars = [("SCRIPTDOCTOOL_FRONTED", "t1x"), ("SCRIPTDOCTOOL_DOCSDIR", "docs")];
+        let errors = config.apply_env(vars.map(|(name, value)| (name.to_string(), value.to_string()))).unwrap_err().into_vec();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|e| matches!(e, Error::Config { file: None, .. })));
+    }
+}
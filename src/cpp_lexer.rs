@@ -0,0 +1,290 @@
+// Minimal C++ tokenizer, only as much as we need to read the gsc sources
+
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TokenKind {
+    Ident,
+    Number,
+    Str,
+    Char,
+    Punct,
+    Comment,
+    Directive,  // whole preprocessor line, e.g. #ifdef COMPILE_SQLITE
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    pub start: usize,   // byte offset into the source
+}
+
+impl<'a> Token<'a> {
+    pub fn is(&self, text: &str) -> bool
+    {
+        matches!(self.kind, TokenKind::Ident | TokenKind::Number | TokenKind::Punct) && self.text == text
+    }
+
+    pub fn end(&self) -> usize
+    {
+        self.start + self.text.len()
+    }
+
+    // Contents of a string literal without the quotes
+    pub fn str_value(&self) -> &'a str
+    {
+        self.text
+            .strip_prefix('"')
+            .and_then(|s| s.strip_suffix('"'))
+            .unwrap_or(self.text)
+    }
+}
+
+pub(crate) fn tokenize(code: &str) -> Vec<Token<'_>>
+{
+    let bytes = code.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let mut line_start = true;
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+
+        let kind = if c.is_ascii_whitespace() {
+            line_start |= c == b'\n';
+            i += 1;
+            continue;
+        } else if c == b'#' && line_start {
+            // Runs to the end of the line (or a trailing comment), following \ continuations
+            while i < bytes.len() && bytes[i] != b'\n' && !bytes[i..].starts_with(b"//") && !bytes[i..].starts_with(b"/*") {
+                if bytes[i] == b'\\' && bytes.get(i + 1) == Some(&b'\n') {
+                    i += 1;
+                }
+                i += 1;
+            }
+            TokenKind::Directive
+        } else if c == b'/' && bytes.get(i + 1) == Some(&b'/') {
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+            TokenKind::Comment
+        } else if c == b'/' && bytes.get(i + 1) == Some(&b'*') {
+            i = code[i + 2..].find("*/").map_or(bytes.len(), |end| i + 2 + end + 2);
+            TokenKind::Comment
+        } else if c == b'"' {
+            i = skip_quoted(bytes, i, b'"');
+            TokenKind::Str
+        } else if c == b'\'' {
+            i = skip_quoted(bytes, i, b'\'');
+            TokenKind::Char
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            // String and char literal prefixes, e.g. L"..", u8'..', R"(..)"
+            match (&code[start..i], bytes.get(i)) {
+                ("R" | "u8R" | "uR" | "UR" | "LR", Some(b'"')) => {
+                    i = skip_raw_string(code, i);
+                    TokenKind::Str
+                }
+                ("u8" | "u" | "U" | "L", Some(b'"')) => {
+                    i = skip_quoted(bytes, i, b'"');
+                    TokenKind::Str
+                }
+                ("u8" | "u" | "U" | "L", Some(b'\'')) => {
+                    i = skip_quoted(bytes, i, b'\'');
+                    TokenKind::Char
+                }
+                _ => TokenKind::Ident,
+            }
+        } else if c.is_ascii_digit() {
+            while i < bytes.len() {
+                let b = bytes[i];
+                // ' is a digit separator (1'000) as long as a digit follows
+                let separator = b == b'\'' && bytes.get(i + 1).is_some_and(u8::is_ascii_alphanumeric);
+                if !(b.is_ascii_alphanumeric() || b == b'.' || b == b'_' || separator) {
+                    break;
+                }
+                i += 1;
+            }
+            TokenKind::Number
+        } else {
+            // Keep multibyte characters whole so slicing stays on char boundaries
+            i += code[i..].chars().next().map_or(1, char::len_utf8);
+            TokenKind::Punct
+        };
+
+        line_start = false;
+        tokens.push(Token {
+            kind,
+            text: &code[start..i],
+            start,
+        });
+    }
+    tokens
+}
+
+// Returns the offset after the closing quote, honouring backslash escapes
+fn skip_quoted(bytes: &[u8], open: usize, quote: u8) -> usize
+{
+    let mut i = open + 1;
+    while i < bytes.len() && bytes[i] != quote {
+        if bytes[i] == b'\\' {
+            i += 1;
+        }
+        i += 1;
+    }
+    (i + 1).min(bytes.len())
+}
+
+// R"delim( ... )delim"
+fn skip_raw_string(code: &str, open: usize) -> usize
+{
+    let rest = &code[open + 1..];
+    let Some(paren) = rest.find('(') else {
+        return code.len();
+    };
+    let closing = format!("){}\"", &rest[..paren]);
+    rest[paren..]
+        .find(&closing)
+        .map_or(code.len(), |end| open + 1 + paren + end + closing.len())
+}
+
+// Splits a directive into its name and the rest, e.g. ("ifdef", "COMPILE_SQLITE")
+pub(crate) fn directive_parts<'a>(token: &Token<'a>) -> (&'a str, &'a str)
+{
+    let text = token.text.trim_start_matches('#').trim_start();
+    let name_len = text.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(text.len());
+    (&text[..name_len], text[name_len..].trim())
+}
+
+// Index of the token closing the bracket opened at tokens[open], e.g. the '}' of a function body
+pub(crate) fn matching_close(tokens: &[Token], open: usize) -> Option<usize>
+{
+    let (open_text, close_text) = match tokens[open].text {
+        "{" => ("{", "}"),
+        "(" => ("(", ")"),
+        "[" => ("[", "]"),
+        _ => return None,
+    };
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        if token.is(open_text) {
+            depth += 1;
+        } else if token.is(close_text) {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+        }
+    }
+    None
+}
+
+// Source text in the given byte range with comments blanked out, newlines are kept
+pub(crate) fn without_comments(code: &str, tokens: &[Token], range: Range<usize>) -> String
+{
+    let mut text = String::new();
+    let mut pos = range.start;
+    for token in tokens {
+        if token.kind != TokenKind::Comment || token.start < range.start || token.end() > range.end {
+            continue;
+        }
+        text.push_str(&code[pos..token.start]);
+        text.extend(token.text.chars().map(|c| if c == '\n' { '\n' } else { ' ' }));
+        pos = token.end();
+    }
+    text.push_str(&code[pos..range.end]);
+    text
+}
+
+// Last token of the statement starting at tokens[start], a braced block or up to the next ;
+pub(crate) fn statement_end(tokens: &[Token], start: usize) -> Option<usize>
+{
+    let first = tokens.get(start)?;
+    if first.is("{") {
+        return matching_close(tokens, start);
+    }
+    let mut i = start;
+    while i < tokens.len() {
+        if tokens[i].is("(") || tokens[i].is("{") || tokens[i].is("[") {
+            i = matching_close(tokens, i)?;
+        } else if tokens[i].is(";") {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+// 1-based line number of a byte offset
+pub(crate) fn line_at(code: &str, offset: usize) -> usize
+{
+    code[..offset].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Index of the '{' opening the body of the only function in code
+    fn body_close(code: &str) -> Option<usize>
+    {
+        let tokens: Vec<Token> = tokenize(code).into_iter().filter(|t| t.kind != TokenKind::Comment).collect();
+        let open = tokens.iter().position(|t| t.is("{"))?;
+        let close = matching_close(&tokens, open)?;
+        Some(tokens[close].start)
+    }
+
+    #[test]
+    fn brackets_in_literals_are_not_code()
+    {
+        let code = "void f() { const char *s = \"{\"; char c = '}'; const char *r = R\"x(})\" })x\"; } int g;";
+        assert_eq!(body_close(code), code.rfind("} int g"));
+
+        let tokens = tokenize(code);
+        let strings: Vec<&str> = tokens.iter().filter(|t| t.kind == TokenKind::Str).map(|t| t.text).collect();
+        assert_eq!(strings, ["\"{\"", "R\"x(})\" })x\""]);
+        assert!(tokens.iter().any(|t| t.kind == TokenKind::Char && t.text == "'}'"));
+    }
+
+    #[test]
+    fn escaped_quotes_stay_in_the_literal()
+    {
+        let code = r#"void f() { Scr_Error("\"}\""); char c = '\''; }"#;
+        assert_eq!(body_close(code), Some(code.len() - 1));
+    }
+
+    #[test]
+    fn brackets_in_comments_are_not_code()
+    {
+        let code = "void f() { // }\n /* } */ return; }";
+        assert_eq!(body_close(code), Some(code.len() - 1));
+        let comments = tokenize(code).iter().filter(|t| t.kind == TokenKind::Comment).count();
+        assert_eq!(comments, 2);
+    }
+
+    #[test]
+    fn directives_run_to_the_end_of_the_line()
+    {
+        let code = "#if defined(A) && \\\n    B\nint x;\n#endif // A";
+        let tokens = tokenize(code);
+        assert_eq!(tokens[0].kind, TokenKind::Directive);
+        assert_eq!(directive_parts(&tokens[0]), ("if", "defined(A) && \\\n    B"));
+        assert!(tokens.iter().any(|t| t.kind == TokenKind::Comment && t.text == "// A"));
+        assert_eq!(line_at(code, code.find("int").unwrap()), 3);
+    }
+
+    #[test]
+    fn directives_with_non_ascii_text()
+    {
+        // U+FFFD is what a Latin-1 byte becomes after a lossy read
+        let code = "#define DEG \"\u{b0}\" // degrees\n#define BAD \"\u{fffd}\"\nint x;";
+        let tokens = tokenize(code);
+        let directives: Vec<&str> = tokens.iter().filter(|t| t.kind == TokenKind::Directive).map(|t| t.text.trim_end()).collect();
+        assert_eq!(directives, ["#define DEG \"\u{b0}\"", "#define BAD \"\u{fffd}\""]);
+        assert!(tokens.iter().any(|t| t.is("x")));
+    }
+}
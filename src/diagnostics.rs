@@ -0,0 +1,108 @@
+use std::collections::{BTreeMap, HashSet};
+
+use crate::source_index::SourceIndex;
+use crate::source_parser::ScriptFunction;
+use crate::{CLEAR_COLOR, BHI_WHITE, B_YELLOW, B_CYAN};
+
+// What happened to registry entries and handlers while parsing
+#[derive(Debug, Default)]
+pub(crate) struct Diagnostics {
+    // Script names dropped by each filter rule
+    pub excluded: BTreeMap<String, Vec<String>>,
+    // (script name, handler) of entries without a handler definition
+    pub undefined: Vec<(String, String)>,
+    // Handlers seen in the registry, filtered or not
+    pub registered: HashSet<String>,
+    // Returned on the ParseResult, printed as they happen when verbose
+    pub warnings: Vec<String>,
+    // (handler, file) of gsc_ handlers in gsc_*.cpp files that gsc.cpp never registers, set by finish
+    pub unregistered: Vec<(String, String)>,
+    // Steps for every --explain name, complete after finish
+    pub traces: BTreeMap<String, Vec<String>>,
+    explain: Vec<String>,
+}
+
+impl Diagnostics {
+    pub fn new(explain: &[String]) -> Diagnostics
+    {
+        Diagnostics {
+            explain: explain.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    // Records a step for --explain if the entry was asked about (by script or handler name)
+    pub fn trace(&mut self, func: &ScriptFunction, step: String)
+    {
+        for name in &self.explain {
+            if *name == func.script_name || *name == func.name {
+                self.traces.entry(name.clone()).or_default().push(step.clone());
+            }
+        }
+    }
+
+    // What can only be told once every registry entry has been resolved
+    pub fn finish(&mut self, index: &SourceIndex)
+    {
+        self.unregistered = index.handlers()
+            .filter(|(name, _)| name.starts_with("gsc_") && !self.registered.contains(*name))
+            .filter_map(|(name, defs)| {
+                let def = defs.iter().find(|d| {
+                    d.file.file_name().and_then(|f| f.to_str()).is_some_and(|f| f.starts_with("gsc_"))
+                })?;
+                Some((name.to_string(), def.file.display().to_string()))
+            })
+            .collect();
+        self.unregistered.sort();
+
+        // Names that never came up while resolving
+        for name in &self.explain {
+            if self.traces.contains_key(name) {
+                continue;
+            }
+            let files: Vec<String> = index.get(name).iter().map(|d| d.file.display().to_string()).collect();
+            let mut steps = vec!["not registered in gsc.cpp as a script name or handler".to_string()];
+            if files.is_empty() {
+                steps.push("no handler with this name in the indexed sources".to_string());
+            } else {
+                steps.push(format!("defined in {}", files.join(", ")));
+            }
+            self.traces.insert(name.clone(), steps);
+        }
+    }
+
+    pub fn report(&self, index: &SourceIndex)
+    {
+        if !self.excluded.is_empty() {
+            println!("{BHI_WHITE}Filtered out{CLEAR_COLOR}");
+            for (rule, names) in &self.excluded {
+                println!("{B_YELLOW}{}{CLEAR_COLOR} by {B_CYAN}{}{CLEAR_COLOR}: {}", names.len(), rule, names.join(", "));
+            }
+            println!();
+        }
+
+        if !self.undefined.is_empty() {
+            println!("{BHI_WHITE}Registered but not defined{CLEAR_COLOR}");
+            for (script_name, handler) in &self.undefined {
+                println!("{B_YELLOW}{}{CLEAR_COLOR} ({}): no definition in {} indexed files", script_name, handler, index.file_count());
+            }
+            println!();
+        }
+
+        if !self.unregistered.is_empty() {
+            println!("{BHI_WHITE}Defined but not registered{CLEAR_COLOR}");
+            for (name, file) in &self.unregistered {
+                println!("{B_YELLOW}{}{CLEAR_COLOR} in {B_CYAN}{}{CLEAR_COLOR}", name, file);
+            }
+            println!();
+        }
+
+        for name in &self.explain {
+            println!("{BHI_WHITE}Explain {B_CYAN}{}{CLEAR_COLOR}", name);
+            for step in self.traces.get(name).into_iter().flatten() {
+                println!("  - {}", step);
+            }
+            println!();
+        }
+    }
+}
@@ -1,16 +1,55 @@
 use std::fs;
 use std::path::Path;
-use crate::source_parser::{ParseResult, ScriptFunction};
+use crate::config::Config;
+use crate::source_parser::{ParseResult, ScriptFunction, ScriptParameter};
+use crate::types::TypeTables;
 use std::path::PathBuf;
 use std::io;
-use io::ErrorKind;
+use crate::error::{Error, Result};
 
 use crate::{CLEAR_COLOR, BHI_WHITE, B_GREEN, B_PURPLE, B_CYAN};
 
-pub fn generate_docs(parse_result: &ParseResult, fail_missing: bool, no_write: bool, write_sep: bool) -> io::Result<()>
+// Default relative to the server directory, holds functions/<category>.rst and methods/<category>.rst
+pub const DOCS_DIR: &str = "docs/source/pages/scripting";
+
+pub struct DocOptions {
+    pub docs_dir: PathBuf,
+    // Link for "Defined in", see DocsConfig
+    pub source_url: Option<String>,
+    // Fail listing the undocumented entries instead of adding stubs
+    pub fail_missing: bool,
+    // Print the stubs instead of appending them
+    pub no_write: bool,
+    // Write the stubs to <name>.temp.rst files instead of appending them
+    pub write_sep: bool,
+    // Print a notice for every stub added, the stubs themselves are printed with no_write either way
+    pub verbose: bool,
+}
+
+impl DocOptions {
+    pub fn new(root: &Path, config: &Config) -> DocOptions
+    {
+        DocOptions {
+            docs_dir: root.join(config.paths.docs_dir.as_deref().unwrap_or(Path::new(DOCS_DIR))),
+            source_url: config.docs.source_url.clone(),
+            fail_missing: false,
+            no_write: false,
+            write_sep: false,
+            verbose: false,
+        }
+    }
+}
+
+pub fn generate_docs(parse_result: &ParseResult, options: &DocOptions) -> Result<()>
 {
-    let base_dir = Path::new("docs/source/pages/scripting");
-    fs::create_dir_all(&base_dir)?;
+    let types = &parse_result.types;
+    let base_dir = options.docs_dir.as_path();
+    let source_url = options.source_url.as_deref();
+    let (fail_missing, no_write, write_sep, verbose) = (options.fail_missing, options.no_write, options.write_sep, options.verbose);
+    for dir in [base_dir.join("functions"), base_dir.join("methods")] {
+        fs::create_dir_all(&dir).map_err(Error::io(&dir))?;
+    }
+    let mut missing: Vec<Error> = Vec::new();
 
     let skip_no_write = format!("{BHI_WHITE}Skipping writing to docs since {B_PURPLE}--no-write {BHI_WHITE}was given.{CLEAR_COLOR}");
     let writing_sep = format!("{BHI_WHITE}Writing to a separate temp file since {B_PURPLE}--write-sep {BHI_WHITE}argument was given.{CLEAR_COLOR}");
@@ -19,41 +58,46 @@ pub fn generate_docs(parse_result: &ParseResult, fail_missing: bool, no_write: b
         let file_path = base_dir.join("functions").join(format!("{}.rst", category));
 
         if !file_path.exists() {
-            fs::write(&file_path, "")?;
+            fs::write(&file_path, "").map_err(Error::io(&file_path))?;
         }
-        let content = fs::read_to_string(&file_path)?;
+        let content = fs::read_to_string(&file_path).map_err(Error::io(&file_path))?;
         let mut template = String::new();
 
-        for (_func_key, func) in funcs {
+        for func in funcs.values() {
             let sign = get_func_sign(&func.script_name);
             if !content.contains(&sign) {
                 let missing_notice = format!("Missing doc for {B_GREEN}{}{CLEAR_COLOR} in {B_CYAN}{}.rst{CLEAR_COLOR}", &func.script_name, &category);
                 if fail_missing {
-                    return Err(io::Error::new(ErrorKind::NotFound, format!("No documentation found for {} in {}.rst", &func.script_name, &category)));
+                    missing.push(Error::MissingDoc { name: func.script_name.clone(), file: file_path.clone() });
+                    continue;
                 }
-                else {
+                else if verbose {
                     println!("{missing_notice}");
                     println!("Adding stub, please edit before commiting.\n");
                 }
-                let func_temp = gen_template(func, false)?;
-                template.push_str(&format!("{}", func_temp));
+                let func_temp = gen_template(func, types, source_url, false)?;
+                template.push_str(&func_temp);
             }
         }
 
         if !template.is_empty() {
             if write_sep {
-                println!("{writing_sep}");
-                let name = template.trim_start().lines().nth(0).unwrap();
+                if verbose {
+                    println!("{writing_sep}");
+                }
+                let name = template.trim_start().lines().next().unwrap_or(category);
                 let file_path = base_dir.join("functions").join(format!("{}.temp.rst", name));
-                fs::write(&file_path, &template)?;
+                fs::write(&file_path, &template).map_err(Error::io(&file_path))?;
                 continue;
             }
             else if no_write {
-                println!("{skip_no_write}");
+                if verbose {
+                    println!("{skip_no_write}");
+                }
                 println!("{}", &template);
                 continue;
             }
-            append_to_file(template, &file_path)?;
+            append_to_file(template, &file_path).map_err(Error::io(&file_path))?;
         }
     }
 
@@ -61,107 +105,223 @@ pub fn generate_docs(parse_result: &ParseResult, fail_missing: bool, no_write: b
         let file_path = base_dir.join("methods").join(format!("{}.rst", category));
 
         if !file_path.exists() {
-            fs::write(&file_path, "")?;
+            fs::write(&file_path, "").map_err(Error::io(&file_path))?;
         }
-        let content = fs::read_to_string(&file_path)?;
+        let content = fs::read_to_string(&file_path).map_err(Error::io(&file_path))?;
         let mut template = String::new();
 
-        for (_func_key, meth) in meths {
+        for meth in meths.values() {
             let sign = get_func_sign(&meth.script_name);
             if !content.contains(&sign) {
                 let missing_notice = format!("Missing doc for {B_GREEN}{}{CLEAR_COLOR} in {B_CYAN}{}.rst{CLEAR_COLOR}", &meth.script_name, &category);
                 if fail_missing {
-                    return Err(io::Error::new(ErrorKind::NotFound, format!("No documentation found for {} in {}.rst", &meth.script_name, &category)));
+                    missing.push(Error::MissingDoc { name: meth.script_name.clone(), file: file_path.clone() });
+                    continue;
                 }
-                else {
+                else if verbose {
                     println!("{missing_notice}");
                     println!("Adding stub, please edit before commiting.\n");
                 }
-                let meth_temp = gen_template(meth, true)?;
-                template.push_str(&format!("{}", meth_temp));
+                let meth_temp = gen_template(meth, types, source_url, true)?;
+                template.push_str(&meth_temp);
             }
         }
 
         if !template.is_empty() {
             if write_sep {
-                println!("{writing_sep}");
-                let name = template.trim_start().lines().nth(0).unwrap();
+                if verbose {
+                    println!("{writing_sep}");
+                }
+                let name = template.trim_start().lines().next().unwrap_or(category);
                 let file_path = base_dir.join("functions").join(format!("{name}.temp.rst"));
-                fs::write(&file_path, &template)?;
+                fs::write(&file_path, &template).map_err(Error::io(&file_path))?;
                 continue;
             }
             else if no_write {
-                println!("{skip_no_write}");
+                if verbose {
+                    println!("{skip_no_write}");
+                }
                 println!("{}", &template);
                 continue;
             }
-            append_to_file(template, &file_path)?;
+            append_to_file(template, &file_path).map_err(Error::io(&file_path))?;
         }
     }
 
+    if !missing.is_empty() {
+        return Err(Error::many(missing));
+    }
     Ok(())
 }
 
 #[rustfmt::skip]
-fn gen_template(func: &ScriptFunction, is_method: bool) -> io::Result<String>
+fn gen_template(func: &ScriptFunction, types: &TypeTables, source_url: Option<&str>, is_method: bool) -> Result<String>
 {
     let mut current = "function".to_string();
     let mut calledon = String::new();
+    let receiver = func.receiver.as_deref().unwrap_or("<some object>");
     if is_method {
         current = "method".to_string();
-        calledon = "<some object> ".to_string();
+        // Variable the example calls the method on
+        let variable = match receiver {
+            "hud element" => "hud",
+            "entity" => "ent",
+            other => other,
+        };
+        calledon = format!("{} ", variable);
     }
     let mut template = String::new();
 
-    //for (_func_key, func) in funcs {
+    //for func in funcs.values() {
         // detect existing entry by scriptName directive
         let sign = get_func_sign(&func.script_name);
 
-        template.push_str(&format!(r#"
-{sign}
+        template.push_str(&format!("\n{sign}\n"));
+        if func.developer {
+            template.push_str("\n.. note:: Developer only, available when ``developer`` is set\n");
+        }
+        if let Some(condition) = &func.condition {
+            template.push_str(&format!("\n.. note:: {}\n", condition_note(condition)));
+        }
 
-.. csv-table:: **Arguments**
+        // One table and one example call per signature
+        // Without a definition there is nothing to infer, same placeholder row as unknown arguments
+        let unknown = [ScriptParameter {
+            param_type: "unknown".to_string(),
+            param_name: "unknown".to_string(),
+            ..Default::default()
+        }];
+        let mut signatures: Vec<&[ScriptParameter]> = vec![func.params.as_deref().unwrap_or(&unknown)];
+        signatures.extend(func.overloads.iter().map(Vec::as_slice));
+        let mut calls: Vec<String> = Vec::new();
+        let mut has_optional = false;
+        for (i, params) in signatures.iter().enumerate() {
+            let title = if signatures.len() > 1 { format!("Arguments (overload {})", i + 1) } else { "Arguments".to_string() };
+            template.push_str(&format!(r#"
+.. csv-table:: **{title}**
     :header: "Argument", "Type", "Description"
     :align: left
 
 "#));
 
-        let mut args = String::new();
-        let mut param_names: Vec<&str> = Vec::new();
-        if let Some(params) = &func.params {
-            for p in params {
-                template.push_str(&format!("    \"{}\", \"{}\", \"description\"\n", p.param_name, p.param_type));
-                param_names.push(&p.param_name)
+            let mut param_names: Vec<String> = Vec::new();
+            for p in *params {
+                let (arg_name, note) = match (p.variadic, p.optional, &p.default) {
+                    (true, _, _) => ("...".to_string(), "(any number of arguments) ".to_string()),
+                    (_, true, Some(default)) => (format!("[{}]", p.param_name), format!("(optional, defaults to ``{}``) ", default)),
+                    (_, true, None) => (format!("[{}]", p.param_name), "(optional) ".to_string()),
+                    _ => (p.param_name.clone(), String::new()),
+                };
+                let description = p.description.as_deref().unwrap_or("description").replace('"', "\"\"");
+                template.push_str(&format!("    \"{}\", \"{}\", \"{}{}\"\n", arg_name, types.display(&p.param_type), note, description));
+                has_optional |= p.optional;
+                param_names.push(arg_name);
             }
+            calls.push(format!("    {}{}({});", &calledon, &func.script_name, param_names.join(", ")));
         }
-        args.push_str(&param_names.join(", "));
+        let optional_note = if has_optional { "\n    // arguments in [brackets] are optional" } else { "" };
 
         if is_method {
-            template.push_str("\n| **Called on** ``<some object>``\n");
+            template.push_str(&format!("\n| **Called on** ``{}``\n", receiver));
         }
         else {
-            template.push_str("\n");
+            template.push('\n');
         }
         for r in &func.returns {
-            template.push_str(&format!("| **Returns** ``{}``\n", r));
+            match func.return_conditions.get(r) {
+                Some(condition) => template.push_str(&format!("| **Returns** ``{}`` {}\n", types.display(r), condition)),
+                None => template.push_str(&format!("| **Returns** ``{}``\n", types.display(r))),
+            }
+        }
+        if let (Some(url), Some(at)) = (source_url, &func.defined_at) {
+            let link = url.replace("{file}", &at.file).replace("{line}", &at.line.to_string());
+            template.push_str(&format!("| **Defined in** `{}:{} <{}>`_\n", at.file, at.line, link));
+        }
+        if !func.errors.is_empty() {
+            template.push_str("\n**Errors**\n\n");
+            for error in &func.errors {
+                template.push_str(&format!("- ``{}``\n", error));
+            }
+        }
+        match &func.description {
+            Some(description) => template.push_str(&format!("\n{}\n", description)),
+            None => template.push_str(&format!("\nthis is the Description of the {}. Explain the usage in detail here\n", &current)),
         }
-        template.push_str(&format!("\nthis is the Description of the {}. Explain the usage in detail here\n", &current));
         template.push_str(&format!(r#"
 **Example**
 
 .. code-block:: cpp
     
     // stub example for dev.
-    // dev. should remove this comment after he is done changing it
-    {}({});
+    // dev. should remove this comment after he is done changing it{}
+{}
 
-"#, &format!("{}{}", &calledon, &func.script_name), &args));
+"#, optional_note, calls.join("\n")));
 
     //}
 
     Ok(template)
 }
 
+// "A && !B && C > 1" -> "Requires build option ``A``, only when ``B`` is disabled and ``C > 1`` is true"
+fn condition_note(condition: &str) -> String
+{
+    let is_name = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    let mut enabled: Vec<String> = Vec::new();
+    let mut disabled: Vec<String> = Vec::new();
+    let mut other: Vec<String> = Vec::new();
+    // Keep "&&" inside parentheses, e.g. "!(A && B)", in one part
+    let mut parts: Vec<String> = Vec::new();
+    for piece in condition.split(" && ") {
+        match parts.last_mut() {
+            Some(last) if last.matches('(').count() > last.matches(')').count() => {
+                last.push_str(" && ");
+                last.push_str(piece);
+            }
+            _ => parts.push(piece.to_string()),
+        }
+    }
+    for part in parts.iter().map(String::as_str) {
+        match part.strip_prefix('!') {
+            Some(name) if is_name(name) => disabled.push(format!("``{}``", name)),
+            _ if is_name(part) => enabled.push(format!("``{}``", part)),
+            _ => other.push(format!("``{}``", part)),
+        }
+    }
+
+    let mut clauses: Vec<String> = Vec::new();
+    if !enabled.is_empty() {
+        let plural = if enabled.len() > 1 { "s" } else { "" };
+        clauses.push(format!("requires build option{} {}", plural, join_and(&enabled)));
+    }
+    let mut when: Vec<String> = Vec::new();
+    for (names, state) in [(&disabled, "disabled"), (&other, "true")] {
+        if !names.is_empty() {
+            let verb = if names.len() > 1 { "are" } else { "is" };
+            when.push(format!("{} {} {}", join_and(names), verb, state));
+        }
+    }
+    if !when.is_empty() {
+        clauses.push(format!("only when {}", when.join(" and ")));
+    }
+    let note = clauses.join(", ");
+    let mut chars = note.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => note,
+    }
+}
+
+// "a", "a and b", "a, b and c"
+fn join_and(items: &[String]) -> String
+{
+    match items {
+        [] => String::new(),
+        [only] => only.clone(),
+        [rest @ .., last] => format!("{} and {}", rest.join(", "), last),
+    }
+}
+
 fn get_func_sign(name: &str) -> String
 {
     format!("{}\n{}", name, "-".repeat(name.len()))
@@ -177,3 +337,18 @@ fn append_to_file(data: String, file_path: &PathBuf) -> io::Result<()>
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn condition_notes()
+    {
+        assert_eq!(condition_note("COMPILE_SQLITE"), "Requires build option ``COMPILE_SQLITE``");
+        assert_eq!(condition_note("!COMPILE_SQLITE"), "Only when ``COMPILE_SQLITE`` is disabled");
+        assert_eq!(condition_note("!COMPILE_SQLITE && USE_MYSQL"), "Requires build option ``USE_MYSQL``, only when ``COMPILE_SQLITE`` is disabled");
+        assert_eq!(condition_note("A && B && !C && X > 2"), "Requires build options ``A`` and ``B``, only when ``C`` is disabled and ``X > 2`` is true");
+        assert_eq!(condition_note("Z && !(D && E)"), "Requires build option ``Z``, only when ``!(D && E)`` is true");
+    }
+}
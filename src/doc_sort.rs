@@ -1,33 +1,81 @@
 use regex::Regex;
-use std::{collections::BTreeMap, fs::read_to_string, fs::write, io, path::PathBuf};
-use io::{Result, ErrorKind};
+use std::{collections::BTreeMap, fs, fs::read_to_string, fs::write, io, path::Path, path::PathBuf};
 
-pub(crate) fn sort(file_path:PathBuf) -> Result<()>
+use crate::cpp_lexer;
+use crate::error::{Error, Result};
+use crate::{CLEAR_COLOR, BHI_WHITE, B_CYAN};
+
+// Sorts every category file in dir, index.rst keeps its order. A file that can't be sorted doesn't stop the others.
+// verbose prints each file as it is sorted
+pub fn sort_dir(dir: &Path, verbose: bool) -> Result<()>
+{
+    let mut errors: Vec<Error> = Vec::new();
+    for entry in fs::read_dir(dir).map_err(Error::io(dir))? {
+        let entry = entry.map_err(Error::io(dir))?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if &file_name == "index.rst" {
+            if verbose {
+                println!("Skipping index.rst");
+            }
+            continue;
+        }
+        if verbose {
+            println!("{BHI_WHITE}Sorting {B_CYAN}{}{CLEAR_COLOR}", &file_name);
+        }
+        if let Err(e) = sort(entry.path()) {
+            errors.push(e);
+        }
+    }
+    if !errors.is_empty() {
+        return Err(Error::many(errors));
+    }
+    Ok(())
+}
+
+pub fn sort(file_path:PathBuf) -> Result<()>
 {
     if !file_path.exists() {
-        return Err(io::Error::new(ErrorKind::NotFound, format!("{} doesn't exist", file_path.to_str().unwrap())));
+        return Err(Error::Io { source: io::Error::new(io::ErrorKind::NotFound, "doesn't exist"), path: file_path });
     }
 
-    let rst_text = read_to_string(&file_path).expect("Failed to read rst file");
+    let rst_text = read_to_string(&file_path).map_err(Error::io(&file_path))?;
 
     let re = Regex::new(r"(?m)^(.+?)\n-{3,}\n").unwrap();
 
     let mut headers = Vec::new();
     for cap in re.captures_iter(&rst_text) {
-        let function_name = cap.get(1).unwrap().as_str().to_string();
+        let Some(function_name) = cap.get(1) else {
+            continue;
+        };
         //println!("{:#?}", cap);
-        headers.push(function_name);
+        headers.push((function_name.as_str().to_string(), cpp_lexer::line_at(&rst_text, function_name.start())));
     }
 
     let pieces: Vec<&str> = re.split(&rst_text).collect();
-    assert!(pieces.len() == headers.len() + 1, "Mismatch between headers and pieces");
+    if pieces.len() != headers.len() + 1 {
+        return Err(Error::DocFormat {
+            file: file_path,
+            line: None,
+            message: format!("found {} section headers but {} sections", headers.len(), pieces.len().saturating_sub(1)),
+        });
+    }
 
     let title = pieces[0];
 
-    // Build a BTreeMap to sort subsections alphabetically
+    // Build a BTreeMap to sort subsections alphabetically, a section written twice would lose one of them
     let mut subsections = BTreeMap::new();
-    for (i, function_name) in headers.iter().enumerate() {
-        subsections.insert(function_name.clone(), pieces[i + 1].to_string());
+    let mut errors: Vec<Error> = Vec::new();
+    for (i, (function_name, line)) in headers.iter().enumerate() {
+        if subsections.insert(function_name.clone(), pieces[i + 1].to_string()).is_some() {
+            errors.push(Error::DocFormat {
+                file: file_path.clone(),
+                line: Some(*line),
+                message: format!("{} is documented more than once", function_name),
+            });
+        }
+    }
+    if !errors.is_empty() {
+        return Err(Error::many(errors));
     }
 
     //println!("{}", serde_json::to_string_pretty(&subsections).unwrap());
@@ -46,7 +94,7 @@ pub(crate) fn sort(file_path:PathBuf) -> Result<()>
         output.push_str(content);
     }
 
-    write(&file_path, &output)?;
+    write(&file_path, &output).map_err(Error::io(&file_path))?;
     //println!("{}", &output);
 
     Ok(())
@@ -0,0 +1,124 @@
+// Everything that can stop a run, with the file and line it is about
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    // Unknown or incomplete command line arguments
+    Usage(String),
+    // Invalid ScriptDocTool.toml, filter rule, frontend or type table, file is None for command line values
+    Config { file: Option<PathBuf>, line: Option<usize>, message: String },
+    Io { path: PathBuf, source: io::Error },
+    // Source the registry can't be read from, e.g. a malformed scriptFunctions[] entry
+    Parse { file: PathBuf, line: Option<usize>, message: String },
+    // Doc files that can't be updated safely, e.g. a section written twice
+    DocFormat { file: PathBuf, line: Option<usize>, message: String },
+    // A builtin without a section with --fail-missing
+    MissingDoc { name: String, file: PathBuf },
+    // Every problem found, never empty or nested
+    Many(Vec<Error>),
+}
+
+impl Error {
+    // For map_err, e.g. fs::read_to_string(&path).map_err(Error::io(&path))
+    pub fn io(path: &Path) -> impl FnOnce(io::Error) -> Error + '_
+    {
+        move |source| Error::Io { path: path.to_path_buf(), source }
+    }
+
+    pub fn many(errors: Vec<Error>) -> Error
+    {
+        let mut errors: Vec<Error> = errors.into_iter().flat_map(Error::into_vec).collect();
+        if errors.len() == 1 {
+            return errors.remove(0);
+        }
+        Error::Many(errors)
+    }
+
+    pub fn into_vec(self) -> Vec<Error>
+    {
+        match self {
+            Error::Many(errors) => errors,
+            error => vec![error],
+        }
+    }
+
+    // Process exit status, one per kind of problem, 1 when several kinds were found
+    pub fn exit_code(&self) -> u8
+    {
+        match self {
+            Error::Usage(_) => 2,
+            Error::Config { .. } => 3,
+            Error::Io { .. } => 4,
+            Error::Parse { .. } => 5,
+            Error::DocFormat { .. } => 6,
+            Error::MissingDoc { .. } => 7,
+            Error::Many(errors) => {
+                let first = errors.first().map_or(1, Error::exit_code);
+                if errors.iter().all(|e| e.exit_code() == first) { first } else { 1 }
+            }
+        }
+    }
+}
+
+// Ok with every value, or every error
+pub fn collect<T>(results: impl IntoIterator<Item = Result<T>>) -> Result<Vec<T>>
+{
+    let mut values = Vec::new();
+    let mut errors = Vec::new();
+    for result in results {
+        match result {
+            Ok(value) => values.push(value),
+            Err(error) => errors.push(error),
+        }
+    }
+    if errors.is_empty() { Ok(values) } else { Err(Error::many(errors)) }
+}
+
+// "path:line: message", like a compiler
+fn location(f: &mut fmt::Formatter, file: &Path, line: Option<usize>) -> fmt::Result
+{
+    match line {
+        Some(line) => write!(f, "{}:{}: ", file.display(), line),
+        None => write!(f, "{}: ", file.display()),
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self {
+            Error::Usage(message) => write!(f, "{}", message),
+            Error::Config { file, line, message } => {
+                if let Some(file) = file {
+                    location(f, file, *line)?;
+                }
+                write!(f, "{}", message)
+            }
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Parse { file, line, message } | Error::DocFormat { file, line, message } => {
+                location(f, file, *line)?;
+                write!(f, "{}", message)
+            }
+            Error::MissingDoc { name, file } => write!(f, "{}: no documentation found for {}", file.display(), name),
+            Error::Many(errors) => {
+                let lines: Vec<String> = errors.iter().map(Error::to_string).collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)>
+    {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
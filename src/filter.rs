@@ -0,0 +1,232 @@
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::config::{Config, ConfigOrigin};
+use crate::error::{self, Error, Result};
+use crate::source_parser::ScriptFunction;
+
+// Part of a registry entry a rule is matched against
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum FilterField {
+    Script,
+    Handler,
+    Category,
+    // Entries flagged developer only in the registry, the rule is just "developer"
+    Developer,
+}
+
+// "[field:]pattern", the pattern is a regex when wrapped in slashes (/^sql_/), a glob otherwise (gsc_test*)
+#[derive(Debug)]
+pub(crate) struct FilterRule {
+    pub spec: String,
+    field: FilterField,
+    re: Regex,
+}
+
+impl FilterRule {
+    pub fn parse(spec: &str) -> Result<FilterRule>
+    {
+        if spec == "developer" {
+            return Ok(FilterRule { spec: spec.to_string(), field: FilterField::Developer, re: Regex::new("").unwrap() });
+        }
+        let (field, pattern) = match spec.split_once(':') {
+            Some(("script", p)) => (FilterField::Script, p),
+            Some(("handler", p)) => (FilterField::Handler, p),
+            Some(("category", p)) => (FilterField::Category, p),
+            _ => (FilterField::Script, spec),
+        };
+        let re_str = match pattern.strip_prefix('/').and_then(|p| p.strip_suffix('/')) {
+            Some(re) => re.to_string(),
+            None => glob_to_regex(pattern),
+        };
+        let re = Regex::new(&re_str)
+            .map_err(|e| Error::Config { file: None, line: None, message: format!("Invalid filter rule '{}': {}", spec, e) })?;
+        Ok(FilterRule { spec: spec.to_string(), field, re })
+    }
+
+    pub fn matches(&self, func: &ScriptFunction, category: &str) -> bool
+    {
+        match self.field {
+            FilterField::Script => self.re.is_match(&func.script_name),
+            FilterField::Handler => self.re.is_match(&func.name),
+            FilterField::Category => self.re.is_match(category),
+            FilterField::Developer => func.developer,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Filters {
+    include: Vec<FilterRule>,
+    exclude: Vec<FilterRule>,
+}
+
+impl Filters {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Filters>
+    {
+        Filters::parse(include, exclude, &ConfigOrigin::default())
+    }
+
+    // The config's [filters] rules, errors point at the rule's line in ScriptDocTool.toml
+    pub fn from_config(config: &Config) -> Result<Filters>
+    {
+        Filters::parse(&config.filters.include, &config.exclude_rules(), &config.origin)
+    }
+
+    fn parse(include: &[String], exclude: &[String], origin: &ConfigOrigin) -> Result<Filters>
+    {
+        let parse = |r: &String| FilterRule::parse(r).map_err(|e| origin.locate(r, e));
+        let include = error::collect(include.iter().map(parse));
+        let exclude = error::collect(exclude.iter().map(parse));
+        match (include, exclude) {
+            (Ok(include), Ok(exclude)) => Ok(Filters { include, exclude }),
+            (include, exclude) => Err(Error::many([include.err(), exclude.err()].into_iter().flatten().collect())),
+        }
+    }
+
+    // Returns the reason an entry is filtered out, None if it is kept
+    pub fn check(&self, func: &ScriptFunction, category: &str) -> Option<String>
+    {
+        if !self.include.is_empty() && !self.include.iter().any(|r| r.matches(func, category)) {
+            return Some("not matched by any include rule".to_string());
+        }
+        self.exclude.iter()
+            .find(|r| r.matches(func, category))
+            .map(|r| format!("exclude {}", r.spec))
+    }
+}
+
+fn glob_to_regex(glob: &str) -> String
+{
+    let mut re = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    re
+}
+
+// Doc category overrides, the first category with a matching rule wins
+#[derive(Debug, Default)]
+pub struct CategoryRules {
+    rules: Vec<(String, Vec<FilterRule>)>,
+}
+
+impl CategoryRules {
+    pub fn new(categories: &BTreeMap<String, Vec<String>>) -> Result<CategoryRules>
+    {
+        CategoryRules::parse(categories, &ConfigOrigin::default())
+    }
+
+    // The config's [categories], errors point at the rule's line in ScriptDocTool.toml
+    pub fn from_config(config: &Config) -> Result<CategoryRules>
+    {
+        CategoryRules::parse(&config.categories, &config.origin)
+    }
+
+    fn parse(categories: &BTreeMap<String, Vec<String>>, origin: &ConfigOrigin) -> Result<CategoryRules>
+    {
+        let rules = error::collect(categories.iter().map(|(category, specs)| {
+            let rules = error::collect(specs.iter().map(|r| FilterRule::parse(r).map_err(|e| origin.locate(r, e))))?;
+            Ok((category.clone(), rules))
+        }))?;
+        Ok(CategoryRules { rules })
+    }
+
+    // Defaults to the name of the file defining the handler (gsc_utils.cpp -> utils),
+    // or the second part of the handler name if that's not a gsc_ file
+    pub fn category_for(&self, func: &ScriptFunction, file: Option<&Path>) -> String
+    {
+        let stem = file
+            .and_then(|f| f.file_stem())
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.strip_prefix("gsc_"));
+        let default = match stem {
+            Some(stem) => stem.to_string(),
+            None => func.name
+                .strip_prefix("gsc_")
+                .and_then(|n| n.split('_').next())
+                .unwrap_or("misc")
+                .to_string(),
+        };
+        self.rules.iter()
+            .find(|(_, rules)| rules.iter().any(|r| r.matches(func, &default)))
+            .map_or(default, |(category, _)| category.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(script_name: &str, handler: &str) -> ScriptFunction
+    {
+        ScriptFunction { script_name: script_name.to_string(), name: handler.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn globs_match_the_whole_name()
+    {
+        let re = Regex::new(&glob_to_regex("gsc_test*")).unwrap();
+        assert!(re.is_match("gsc_test_foo"));
+        assert!(!re.is_match("gsc_utils_testfoo"));
+        assert_eq!(glob_to_regex("a?.b"), r"^a.\.b$");
+    }
+
+    #[test]
+    fn rules_pick_the_field_and_pattern()
+    {
+        let testfoo = entry("testfoo", "gsc_utils_testfoo");
+        assert!(FilterRule::parse("test*").unwrap().matches(&testfoo, "utils"));
+        assert!(!FilterRule::parse("handler:test*").unwrap().matches(&testfoo, "utils"));
+        assert!(FilterRule::parse("handler:/_test/").unwrap().matches(&testfoo, "utils"));
+        assert!(FilterRule::parse("category:util?").unwrap().matches(&testfoo, "utils"));
+        // Unknown prefixes are part of a script name pattern
+        assert!(!FilterRule::parse("file:test*").unwrap().matches(&testfoo, "utils"));
+    }
+
+    #[test]
+    fn invalid_regex_is_a_config_error()
+    {
+        assert!(matches!(FilterRule::parse("/[/"), Err(Error::Config { .. })));
+        let errors = Filters::new(&["/[/".to_string()], &["/(/".to_string()]).unwrap_err().into_vec();
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn include_then_exclude()
+    {
+        let filters = Filters::new(&["handler:gsc_utils_*".to_string()], &["handler:gsc_utils_test*".to_string()]).unwrap();
+        assert_eq!(filters.check(&entry("sqrt", "gsc_utils_sqrt"), "utils"), None);
+        assert_eq!(filters.check(&entry("open", "gsc_sqlite_open"), "sqlite").as_deref(), Some("not matched by any include rule"));
+        assert_eq!(filters.check(&entry("testfoo", "gsc_utils_testfoo"), "utils").as_deref(), Some("exclude handler:gsc_utils_test*"));
+    }
+
+    #[test]
+    fn categories_from_files_and_rules()
+    {
+        let rules = CategoryRules::new(&BTreeMap::from([("weapons".to_string(), vec!["handler:gsc_custom_weapons_*".to_string()])])).unwrap();
+        let sqrt = entry("sqrt", "gsc_utils_sqrt");
+        assert_eq!(rules.category_for(&sqrt, Some(Path::new("src/gsc/gsc_math_ext.cpp"))), "math_ext");
+        assert_eq!(rules.category_for(&sqrt, Some(Path::new("src/gsc/helpers.h"))), "utils");
+        assert_eq!(rules.category_for(&sqrt, None), "utils");
+        assert_eq!(rules.category_for(&entry("getammo", "gsc_custom_weapons_getammo"), None), "weapons");
+    }
+
+    #[test]
+    fn developer_rule()
+    {
+        let mut devonly = entry("devonly", "gsc_utils_devonly");
+        devonly.developer = true;
+        let filters = Filters::new(&[], &["developer".to_string()]).unwrap();
+        assert_eq!(filters.check(&devonly, "utils").as_deref(), Some("exclude developer"));
+        assert_eq!(filters.check(&entry("sqrt", "gsc_utils_sqrt"), "utils"), None);
+        // Only the exact word, anything else is a script name pattern
+        assert!(!FilterRule::parse("developers").unwrap().matches(&devonly, "utils"));
+    }
+}
@@ -0,0 +1,67 @@
+// Reads a server codebase into the script functions and methods to document
+
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::source_parser::{self, ParseOptions, ParseResult, RegistryLayout};
+
+pub trait Frontend {
+    // Type table preset used unless [types] preset says otherwise
+    fn type_preset(&self) -> &str;
+    fn parse(&self, options: &ParseOptions) -> Result<ParseResult>;
+}
+
+// Codebases registering builtins in arrays of {"name", handler, developer} entries, e.g.
+//     scr_function_t scriptFunctions[] = { {"printf", gsc_utils_printf, 0}, ... };
+pub struct RegistryFrontend {
+    type_preset: &'static str,
+    layout: RegistryLayout,
+}
+
+impl RegistryFrontend {
+    pub fn new(type_preset: &'static str, layout: RegistryLayout) -> RegistryFrontend
+    {
+        RegistryFrontend { type_preset, layout }
+    }
+}
+
+impl Frontend for RegistryFrontend {
+    fn type_preset(&self) -> &str
+    {
+        self.type_preset
+    }
+
+    fn parse(&self, options: &ParseOptions) -> Result<ParseResult>
+    {
+        source_parser::parse(&self.layout, options)
+    }
+}
+
+// The layout of config's frontend with the [paths] and [arrays] overrides on top
+pub fn from_config(config: &Config) -> Result<Box<dyn Frontend>>
+{
+    let name = config.frontend_name();
+    // gsc.cpp is at the top of the source directory in every layout.
+    // iw1x is the t1x layout under its own name until the two forks differ
+    let (type_preset, src_dir, arrays) = match name {
+        "t1x" => ("t1x", "src/gsc", ("scriptFunctions", "scriptMethods")),
+        "iw1x" => ("iw1x", "src/gsc", ("scriptFunctions", "scriptMethods")),
+        // CoD2 libcod keeps gsc.cpp and the gsc_*.cpp files at the top of the repo
+        "libcod" => ("libcod", ".", ("scriptFunctions", "scriptMethods")),
+        _ => {
+            return Err(config.origin.locate(name, Error::Config {
+                file: None,
+                line: None,
+                message: format!("Unknown frontend '{}', expected t1x, iw1x or libcod", name),
+            }));
+        }
+    };
+    let src_dir = config.paths.src_dir.clone().unwrap_or_else(|| PathBuf::from(src_dir));
+    Ok(Box::new(RegistryFrontend::new(type_preset, RegistryLayout {
+        registry: config.paths.registry.clone().unwrap_or_else(|| src_dir.join("gsc.cpp")),
+        src_dir,
+        function_array: config.arrays.functions.clone().unwrap_or_else(|| arrays.0.to_string()),
+        method_array: config.arrays.methods.clone().unwrap_or_else(|| arrays.1.to_string()),
+    })))
+}
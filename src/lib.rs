@@ -0,0 +1,46 @@
+// Parses a CoD server codebase into its GSC builtins and keeps their docs in sync.
+// Everything takes the server directory and options explicitly, the ScriptDocTool binary is a wrapper over this.
+
+pub const CLEAR_COLOR: &str = "\x1b[0m";
+pub const BHI_WHITE: &str = "\x1b[1;97m";
+pub const B_RED: &str = "\x1b[1;31m";
+pub const B_GREEN: &str = "\x1b[1;32m";
+pub const B_YELLOW: &str = "\x1b[1;33m";
+pub const B_PURPLE: &str = "\x1b[1;35m";
+pub const B_CYAN: &str = "\x1b[1;36m";
+
+mod arity;
+mod cache;
+mod config;
+mod cpp_lexer;
+mod diagnostics;
+mod error;
+mod filter;
+mod frontend;
+mod progress;
+mod returns;
+mod source_index;
+mod types;
+mod source_parser;
+mod doc_gen;
+mod doc_sort;
+
+pub use config::{ArraysConfig, Config, ConfigOrigin, DocsConfig, FilterConfig, PathsConfig, TypesConfig, CONFIG_FILE};
+pub use error::{Error, Result};
+pub use doc_gen::{generate_docs, DocOptions, DOCS_DIR};
+pub use doc_sort::{sort, sort_dir};
+pub use filter::{CategoryRules, Filters};
+pub use frontend::{from_config as frontend_from_config, Frontend, RegistryFrontend};
+pub use source_parser::{ParseOptions, ParseResult, RegistryLayout, ScriptFunction, ScriptParameter, SourceLocation};
+pub use cache::CACHE_FILE;
+pub use types::TypeTables;
+
+use std::path::Path;
+
+// Parses the server at root with the frontend, filters and types of config
+pub fn parse(root: &Path, config: &Config) -> Result<ParseResult>
+{
+    let frontend = frontend::from_config(config)?;
+    let options = ParseOptions::new(root, config, frontend.type_preset())?;
+    frontend.parse(&options)
+}
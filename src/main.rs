@@ -1,33 +1,55 @@
 
-const CLEAR_COLOR: &str = "\x1b[0m";
-const BHI_WHITE: &str = "\x1b[1;97m";
-const B_GREEN: &str = "\x1b[1;32m";
-const B_YELLOW: &str = "\x1b[1;33m";
-const B_PURPLE: &str = "\x1b[1;35m";
-const B_CYAN: &str = "\x1b[1;36m";
-
-mod source_parser;
-mod doc_gen;
-mod doc_sort;
-use source_parser::ParseResult;
-use doc_gen::generate_docs;
-use doc_sort::sort;
+use script_doc_tool::{frontend_from_config, generate_docs, sort_dir, Config, DocOptions, Error, ParseOptions, ParseResult, Result, CACHE_FILE};
+use script_doc_tool::{CLEAR_COLOR, BHI_WHITE, B_RED, B_GREEN, B_PURPLE};
 use std::env;
-use std::io;
-use std::fs;
-use std::path::Path;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
-fn main() -> io::Result<()>
+fn main() -> ExitCode
 {
     println!("{B_GREEN}Free Palestine{CLEAR_COLOR} 🍉️ 🇵🇸️ \n\n");
+    // Panics are reported below as errors, not with the default message and backtrace note
+    panic::set_hook(Box::new(|_| {}));
+    match panic::catch_unwind(run) {
+        Ok(Ok(())) => ExitCode::SUCCESS,
+        Err(payload) => {
+            let message = match payload.downcast::<String>() {
+                Ok(message) => *message,
+                Err(payload) => payload.downcast_ref::<&str>().map_or("unknown panic", |m| m).to_string(),
+            };
+            eprintln!("{B_RED}error{CLEAR_COLOR}: internal error, please report it: {}", message);
+            ExitCode::from(101)
+        }
+        Ok(Err(error)) => {
+            let code = error.exit_code();
+            for error in error.into_vec() {
+                eprintln!("{B_RED}error{CLEAR_COLOR}: {}", error);
+            }
+            ExitCode::from(code)
+        }
+    }
+}
+
+fn run() -> Result<()>
+{
     let mut parse_only = false;
     let mut print_parsed = false;
     let mut fail_missing = false;
     let mut no_write = false;
     let mut write_sep = false;
     let mut sortdoc = false;
+    let mut use_cache = true;
+    let mut include: Vec<String> = Vec::new();
+    let mut exclude: Vec<String> = Vec::new();
+    let mut explain: Vec<String> = Vec::new();
+    let mut root: Option<PathBuf> = None;
+    let mut src_dir: Option<PathBuf> = None;
+    let mut docs_dir: Option<PathBuf> = None;
+    let mut usage: Vec<Error> = Vec::new();
 
-    for arg in env::args() {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
         match arg.as_str() {
             "--parse-only" => parse_only = true,
             "--print-parsed" => print_parsed = true,
@@ -35,44 +57,92 @@ fn main() -> io::Result<()>
             "--no-write" => no_write = true,
             "--write-sep" => write_sep = true,
             "--sort" => sortdoc = true,
-            val if val == env::args().nth(0).unwrap().as_str() => continue,
-            _ => eprintln!("Unknow argument: {}", arg),
+            "--no-cache" => use_cache = false,
+            "--include" | "--exclude" => match args.next() {
+                Some(rule) if arg == "--include" => include.push(rule),
+                Some(rule) => exclude.push(rule),
+                None => usage.push(Error::Usage(format!("Missing rule after {}", arg))),
+            },
+            "--explain" => match args.next() {
+                Some(name) => explain.push(name),
+                None => usage.push(Error::Usage(format!("Missing name after {}", arg))),
+            },
+            "--root" | "--src-dir" | "--docs-dir" => match args.next() {
+                Some(dir) if arg == "--root" => root = Some(PathBuf::from(dir)),
+                Some(dir) if arg == "--src-dir" => src_dir = Some(PathBuf::from(dir)),
+                Some(dir) => docs_dir = Some(PathBuf::from(dir)),
+                None => usage.push(Error::Usage(format!("Missing directory after {}", arg))),
+            },
+            _ => usage.push(Error::Usage(format!("Unknown argument: {}", arg))),
         }
     }
+    if !usage.is_empty() {
+        return Err(Error::many(usage));
+    }
+
+    // Command line over environment over ScriptDocTool.toml, command line paths are relative to the working directory
+    let cwd = env::current_dir().map_err(Error::io(Path::new(".")))?;
+    let (config_dir, mut config) = match &root {
+        Some(root) => (cwd.join(root), Config::load(&cwd.join(root))?),
+        None => Config::find(&cwd)?,
+    };
+    let vars = env::vars_os().filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)));
+    config.apply_env(vars)?;
+    let root = match &root {
+        Some(_) => config_dir,
+        None => config.root(&config_dir),
+    };
+    if let Some(src_dir) = src_dir {
+        config.paths.src_dir = Some(relative_to(cwd.join(src_dir), &root));
+    }
+    if let Some(docs_dir) = docs_dir {
+        config.paths.docs_dir = Some(relative_to(cwd.join(docs_dir), &root));
+    }
+    // Shown relative when run from inside the server directory, empty rather than "." so joined paths stay short
+    let root = root.strip_prefix(&cwd).map(Path::to_path_buf).unwrap_or(root);
+    let root = root.as_path();
+
+    let mut doc_options = DocOptions::new(root, &config);
+    doc_options.fail_missing = fail_missing;
+    doc_options.no_write = no_write;
+    doc_options.write_sep = write_sep;
+    doc_options.verbose = true;
 
     if sortdoc {
-        let mut dirs: Vec<&Path> = Vec::new();
-        dirs.push(Path::new("docs/source/pages/scripting/functions"));
-        dirs.push(Path::new("docs/source/pages/scripting/methods"));
-
-        for dir in dirs {
-            let entries = fs::read_dir(dir)?;
-            for entry in entries {
-                let entry = entry?;
-                let file_name = entry.file_name().to_string_lossy().to_string();
-                if &file_name == "index.rst" {
-                    println!("Skipping index.rst");
-                    continue;
-                }
-                println!("{BHI_WHITE}Sorting {B_CYAN}{}{CLEAR_COLOR}", &file_name);
-                sort(entry.path())?;
-            }
-        }
-        println!("");
+        let functions = sort_dir(&doc_options.docs_dir.join("functions"), true);
+        let methods = sort_dir(&doc_options.docs_dir.join("methods"), true);
+        println!();
+
+        return match (functions, methods) {
+            (Ok(()), Ok(())) => Ok(()),
+            (functions, methods) => Err(Error::many([functions.err(), methods.err()].into_iter().flatten().collect())),
+        };
+    }
 
-        return Ok(())
+    // Rules from the command line come before the config ones
+    include.extend(config.filters.include.iter().cloned());
+    exclude.extend(config.exclude_rules());
+    config.filters.include = include;
+    config.filters.exclude = Some(exclude);
+
+    let frontend = frontend_from_config(&config)?;
+    let mut options = ParseOptions::new(root, &config, frontend.type_preset())?;
+    options.explain = explain;
+    options.verbose = true;
+    if use_cache {
+        options.cache_file = Some(root.join(CACHE_FILE));
     }
 
-    let data: ParseResult = source_parser::parse()?;
+    let data: ParseResult = frontend.parse(&options)?;
 
     if print_parsed {
-        println!("Functions: \n{}", serde_json::to_string_pretty(&data.functions).unwrap());
-        println!("Methods: \n{}", serde_json::to_string_pretty(&data.methods).unwrap());
-        println!("");
+        println!("Functions: \n{}", serde_json::to_string_pretty(&data.functions).unwrap_or_else(|e| e.to_string()));
+        println!("Methods: \n{}", serde_json::to_string_pretty(&data.methods).unwrap_or_else(|e| e.to_string()));
+        println!();
     }
 
     if !parse_only {
-        generate_docs(&data, fail_missing, no_write, write_sep).unwrap();
+        generate_docs(&data, &doc_options)?;
     }
     else {
         println!("{BHI_WHITE}Skipping doc generation since {B_PURPLE}--parse-only {BHI_WHITE}argument was given.{CLEAR_COLOR}");
@@ -80,3 +150,27 @@ fn main() -> io::Result<()>
 
     Ok(())
 }
+
+// path without the base prefix, unchanged outside base and "." for base itself
+fn relative_to(path: PathBuf, base: &Path) -> PathBuf
+{
+    match path.strip_prefix(base) {
+        Ok(rest) if rest.as_os_str().is_empty() => PathBuf::from("."),
+        Ok(rest) => rest.to_path_buf(),
+        Err(_) => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_relative_to_the_working_directory()
+    {
+        let base = Path::new("/srv/t1x");
+        assert_eq!(relative_to(PathBuf::from("/srv/t1x/src/gsc"), base), PathBuf::from("src/gsc"));
+        assert_eq!(relative_to(PathBuf::from("/srv/t1x"), base), PathBuf::from("."));
+        assert_eq!(relative_to(PathBuf::from("/srv/libcod"), base), PathBuf::from("/srv/libcod"));
+    }
+}
@@ -0,0 +1,50 @@
+// One updating status line on a terminal, plain lines when the output is piped or logged
+
+use std::io::{self, IsTerminal, Write};
+
+pub(crate) struct Progress {
+    enabled: bool,
+    interactive: bool,
+    width: usize,
+}
+
+impl Progress {
+    // Prints nothing unless enabled
+    pub fn new(enabled: bool) -> Progress
+    {
+        let interactive = io::stdout().is_terminal();
+        // termsize can fail even on a terminal (e.g. a serial console), assume 80 columns then
+        let width = termsize::get().map_or(80, |size| size.cols as usize);
+        Progress { enabled, interactive, width }
+    }
+
+    // Shows what is being worked on, only on a terminal since it may be called from any thread
+    pub fn status(&self, name: &str)
+    {
+        if !self.enabled || !self.interactive {
+            return;
+        }
+        // Keep the line shorter than the terminal so it never wraps
+        let room = self.width.saturating_sub(4);
+        let name: String = name.chars().take(room).collect();
+        print!("\r{:<width$}", format!("{}...", name), width = self.width.saturating_sub(1));
+        let _ = io::stdout().flush();
+    }
+
+    // The plain line for logs and pipes, printed in a stable order by the caller
+    pub fn log(&self, name: &str)
+    {
+        if self.enabled && !self.interactive {
+            println!("{}...", name);
+        }
+    }
+
+    // Clears the status line so the next output starts on an empty line
+    pub fn finish(&self)
+    {
+        if self.enabled && self.interactive {
+            print!("\r{}\r", " ".repeat(self.width.saturating_sub(1)));
+            let _ = io::stdout().flush();
+        }
+    }
+}
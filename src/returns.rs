@@ -0,0 +1,241 @@
+// Works out what a handler leaves on the script stack (Scr_Add* calls)
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+use crate::cpp_lexer::{self, Token, TokenKind};
+use crate::types::TypeTables;
+
+// Returned when the handler fails, listed after the real return types
+const FAILURE_VALUES: &[&str] = &["undefined"];
+
+// A value pushed by the handler, arrays collect the types added to them
+#[derive(Debug)]
+enum Value {
+    Simple(String),
+    Array(BTreeSet<String>),
+}
+
+impl Value {
+    // "int", "array", "array of string", "array of int or string"
+    fn type_name(&self) -> String
+    {
+        match self {
+            Value::Simple(ty) => ty.clone(),
+            Value::Array(elements) if elements.is_empty() => "array".to_string(),
+            Value::Array(elements) => format!("array of {}", elements.iter().cloned().collect::<Vec<_>>().join(" or ")),
+        }
+    }
+}
+
+// if (condition) ... else ...
+struct IfStatement {
+    condition: String,
+    then_range: Range<usize>,
+    else_range: Option<Range<usize>>,
+}
+
+pub(crate) struct ReturnInfo {
+    pub types: Vec<String>,
+    // Why a return type only happens sometimes, e.g. "undefined" -> "if the arguments are invalid"
+    pub conditions: BTreeMap<String, String>,
+}
+
+pub(crate) fn infer(body: &str, types: &TypeTables) -> ReturnInfo
+{
+    let tokens: Vec<Token> = cpp_lexer::tokenize(body)
+        .into_iter()
+        .filter(|t| t.kind != TokenKind::Comment)
+        .collect();
+
+    // Replays the Scr_* calls in source order like the script stack would, branches and loops once each:
+    //     Scr_MakeArray(); for (...) { Scr_AddString(s); Scr_AddArray(); }
+    // leaves one array of string
+    let mut stack: Vec<(usize, Value)> = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if token.kind != TokenKind::Ident || !tokens.get(i + 1).is_some_and(|t| t.is("(")) {
+            continue;
+        }
+        match token.text {
+            name if types.is_make_array(name) => stack.push((token.start, Value::Array(BTreeSet::new()))),
+            name if types.is_add_to_array(name) => {
+                let Some((pos, value)) = stack.pop() else {
+                    continue;
+                };
+                let element = value.type_name();
+                match stack.iter_mut().rev().find_map(|(_, v)| match v {
+                    Value::Array(elements) => Some(elements),
+                    Value::Simple(_) => None,
+                }) {
+                    Some(elements) => {
+                        elements.insert(element);
+                    }
+                    // Array made somewhere else, e.g. in a helper
+                    None => stack.push((pos, Value::Array(BTreeSet::from([element])))),
+                }
+            }
+            name => {
+                if let Some(ty) = types.scr_add(name) {
+                    stack.push((token.start, Value::Simple(ty)));
+                }
+            }
+        }
+    }
+
+    let ifs = if_statements(body, &tokens);
+    let mut return_types: Vec<String> = Vec::new();
+    let mut conditions: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (pos, value) in &stack {
+        let ty = value.type_name();
+        if ty == "undefined" && let Some(condition) = explain_condition(&ifs, *pos) {
+            let reasons = conditions.entry(ty.clone()).or_default();
+            if !reasons.contains(&condition) {
+                reasons.push(condition);
+            }
+        }
+        if !return_types.contains(&ty) {
+            return_types.push(ty);
+        }
+    }
+
+    if return_types.is_empty() {
+        return_types.push("unknown".to_string());
+    }
+    sort_return_types(&mut return_types);
+    ReturnInfo {
+        types: return_types,
+        conditions: conditions.into_iter().map(|(ty, reasons)| (ty, reasons.join(", or "))).collect(),
+    }
+}
+
+// Primary types first in the order the handler returns them, failure values last
+pub(crate) fn sort_return_types(return_types: &mut [String])
+{
+    return_types.sort_by_key(|ty| FAILURE_VALUES.contains(&ty.as_str()));
+}
+
+fn if_statements(body: &str, tokens: &[Token]) -> Vec<IfStatement>
+{
+    let mut statements = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if !token.is("if") || !tokens.get(i + 1).is_some_and(|t| t.is("(")) {
+            continue;
+        }
+        let Some(close) = cpp_lexer::matching_close(tokens, i + 1) else {
+            continue;
+        };
+        let Some(then_end) = cpp_lexer::statement_end(tokens, close + 1) else {
+            continue;
+        };
+        let else_range = if tokens.get(then_end + 1).is_some_and(|t| t.is("else")) {
+            cpp_lexer::statement_end(tokens, then_end + 2).map(|end| tokens[then_end + 2].start..tokens[end].end())
+        } else {
+            None
+        };
+        let condition = body[tokens[i + 1].end()..tokens[close].start].split_whitespace().collect::<Vec<_>>().join(" ");
+        statements.push(IfStatement {
+            condition,
+            then_range: tokens[close + 1].start..tokens[then_end].end(),
+            else_range,
+        });
+    }
+    statements
+}
+
+// Reads the innermost if around pos as the reason the value is returned
+fn explain_condition(ifs: &[IfStatement], pos: usize) -> Option<String>
+{
+    let (statement, in_then) = ifs.iter()
+        .filter_map(|s| {
+            if s.then_range.contains(&pos) {
+                Some((s, true, s.then_range.len()))
+            } else if s.else_range.as_ref().is_some_and(|r| r.contains(&pos)) {
+                Some((s, false, s.else_range.as_ref()?.len()))
+            } else {
+                None
+            }
+        })
+        .min_by_key(|(_, _, len)| *len)
+        .map(|(s, in_then, _)| (s, in_then))?;
+
+    let condition = &statement.condition;
+    if in_then && condition.starts_with('!') && (condition.contains("stackGetParam") || condition.contains("Scr_Get")) {
+        return Some("if the arguments are invalid".to_string());
+    }
+    Some(if in_then { format!("if ``{}``", condition) } else { format!("unless ``{}``", condition) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn return_types(body: &str) -> ReturnInfo
+    {
+        infer(body, &TypeTables::preset("t1x").unwrap())
+    }
+
+    #[test]
+    fn array_of_string()
+    {
+        let info = return_types(r#"
+    Scr_MakeArray();
+    for (int i = 0; i < count; i++) {
+        Scr_AddString(names[i]);
+        Scr_AddArray();
+    }
+"#);
+        assert_eq!(info.types, ["array of string"]);
+    }
+
+    #[test]
+    fn array_with_mixed_elements()
+    {
+        let info = return_types("Scr_MakeArray(); Scr_AddInt(1); Scr_AddArray(); Scr_AddString(s); Scr_AddArray();");
+        assert_eq!(info.types, ["array of int or string"]);
+    }
+
+    #[test]
+    fn undefined_explained_by_the_failing_check()
+    {
+        let info = return_types(r#"
+    if (!stackGetParams("s", &path)) {
+        Scr_AddUndefined();
+        return;
+    }
+    FILE *file = fopen(path, "r");
+    if (file == NULL)
+        Scr_AddUndefined();
+    else
+        Scr_AddInt(size);
+"#);
+        assert_eq!(info.conditions.get("undefined").map(String::as_str), Some("if the arguments are invalid, or if ``file == NULL``"));
+        let info = return_types("if (ok) Scr_AddBool(1); else Scr_AddUndefined();");
+        assert_eq!(info.conditions.get("undefined").map(String::as_str), Some("unless ``ok``"));
+    }
+
+    #[test]
+    fn nothing_pushed_is_unknown()
+    {
+        assert_eq!(return_types("int x = 1;").types, ["unknown"]);
+    }
+
+    #[test]
+    fn primary_types_first_failure_values_last()
+    {
+        let info = return_types(r#"
+    if (!stackGetParams("s", &path)) {
+        Scr_AddUndefined();
+        return;
+    }
+    if (exists)
+        Scr_AddString(path);
+    else
+        Scr_AddBool(0);
+"#);
+        assert_eq!(info.types, ["string", "bool", "undefined"]);
+
+        let mut types = vec!["undefined".to_string(), "int".to_string(), "array of string".to_string()];
+        sort_return_types(&mut types);
+        assert_eq!(types, ["int", "array of string", "undefined"]);
+    }
+}
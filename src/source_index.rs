@@ -0,0 +1,415 @@
+use std::collections::{HashMap, HashSet};
+use std::any::Any;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::cache::{fnv1a, Cache};
+use crate::cpp_lexer::{self, Token, TokenKind};
+use crate::error::{self, Error, Result};
+use crate::progress::Progress;
+use crate::source_parser::{analyze_handler, parse_source_file, FunctionDef, ScriptFunctionDetails, SourceFile};
+use crate::types::TypeTables;
+
+const SOURCE_EXTENSIONS: &[&str] = &["cpp", "cc", "cxx", "c", "hpp", "hh", "h"];
+
+// Build output and third party code, never handlers. Hidden directories (.git) are skipped too
+const SKIPPED_DIRS: &[&str] = &["build", "bin", "obj", "out", "target", "vendor", "vendors", "third_party", "node_modules"];
+
+// How many calls deep helpers are followed from a handler
+const MAX_HELPER_DEPTH: usize = 3;
+
+// The stack API itself is what the analysis looks for, never inline it
+const API_PREFIXES: &[&str] = &["Scr_", "stack"];
+
+#[derive(Debug)]
+pub(crate) struct HandlerDef {
+    pub file: PathBuf,
+    pub line: usize,
+    pub details: ScriptFunctionDetails,
+    // Functions whose code was inlined into the handler, in call order
+    pub helpers: Vec<String>,
+}
+
+// Every handler definition found under the source tree and the headers it includes
+#[derive(Debug, Default)]
+pub(crate) struct SourceIndex {
+    handlers: HashMap<String, Vec<HandlerDef>>,
+    files: Vec<PathBuf>,
+}
+
+impl SourceIndex {
+    pub fn build(src_dir: &Path, types: &TypeTables, cache: &mut Cache, progress: &Progress) -> Result<SourceIndex>
+    {
+        let mut index = SourceIndex::default();
+        let mut queue: Vec<PathBuf> = Vec::new();
+        collect_source_files(src_dir, &mut queue)?;
+        queue.sort();
+        let mut seen: HashSet<PathBuf> = queue.iter().filter_map(|p| p.canonicalize().ok()).collect();
+
+        let mut sources: Vec<(PathBuf, Vec<FunctionDef>)> = Vec::new();
+        let mut start = 0;
+        while start < queue.len() {
+            // Files of the tree first, then each round of headers they include
+            let wave: Vec<PathBuf> = queue[start..].to_vec();
+            start = queue.len();
+
+            let mut files: Vec<(String, u64, Option<SourceFile>)> = Vec::new();
+            for path in &wave {
+                // Old sources are often Latin-1, only the code around the handlers has to be readable
+                let bytes = fs::read(path).map_err(Error::io(path))?;
+                let hash = fnv1a(&[&bytes]);
+                let code = String::from_utf8_lossy(&bytes).into_owned();
+                let cached = cache.source_file(path, hash);
+                files.push((code, hash, cached));
+            }
+            let parsed = parallel_map(&files, |(code, _, cached)| cached.is_none().then(|| parse_source_file(code)));
+            let parsed = error::collect(wave.iter().zip(parsed).map(|(path, parsed)| {
+                parsed.map_err(|message| internal_error(path, None, message))
+            }))?;
+
+            for ((path, (_, hash, cached)), parsed) in wave.into_iter().zip(files).zip(parsed) {
+                let Some(source) = cached.or(parsed) else {
+                    continue;
+                };
+                cache.store_source_file(&path, hash, &source);
+
+                // Follow #include "..." into headers outside the tree
+                for include in &source.includes {
+                    let dirs = [path.parent().unwrap_or(Path::new("")), src_dir, src_dir.parent().unwrap_or(Path::new(""))];
+                    let Some(header) = dirs.iter().map(|d| d.join(include)).find(|p| p.is_file()) else {
+                        continue;
+                    };
+                    if header.canonicalize().is_ok_and(|c| seen.insert(c)) {
+                        queue.push(header);
+                    }
+                }
+
+                sources.push((path, source.functions));
+            }
+        }
+
+        // Any function can be a helper, the first definition of a name wins
+        let mut bodies: HashMap<&str, &str> = HashMap::new();
+        for (_, functions) in &sources {
+            for function in functions {
+                bodies.entry(function.name.as_str()).or_insert(function.body.as_str());
+            }
+        }
+
+        let handlers: Vec<(&PathBuf, &FunctionDef)> = sources.iter()
+            .flat_map(|(path, functions)| functions.iter().filter(|f| f.is_handler).map(move |f| (path, f)))
+            .collect();
+        let inlined = parallel_map(&handlers, |(_, function)| {
+            let mut helpers: Vec<String> = Vec::new();
+            let mut calling = vec![function.name.clone()];
+            let body = inline_helpers(&function.body, &bodies, &mut calling, &mut helpers);
+            // Helpers are part of the key, a changed helper means analyzing the handler again
+            let comment = function.comment.as_deref().unwrap_or("");
+            let hash = fnv1a(&[function.name.as_bytes(), comment.as_bytes(), body.as_bytes()]);
+            (helpers, body, hash)
+        });
+        let inlined = error::collect(handlers.iter().zip(inlined).map(|((path, function), inlined)| {
+            inlined.map_err(|message| internal_error(path, Some(function.line), message))
+        }))?;
+        let cached: Vec<Option<ScriptFunctionDetails>> = inlined.iter().map(|(_, _, hash)| cache.handler(*hash)).collect();
+
+        let jobs: Vec<_> = handlers.iter().zip(&inlined).zip(&cached).collect();
+        let analyzed = parallel_map(&jobs, |(((_, function), (_, body, _)), cached)| {
+            cached.is_none().then(|| {
+                progress.status(&function.name);
+                analyze_handler(body, function.comment.as_deref(), types)
+            })
+        });
+        let analyzed = error::collect(handlers.iter().zip(analyzed).map(|((path, function), analyzed)| {
+            analyzed.map_err(|message| internal_error(path, Some(function.line), message))
+        }))?;
+
+        // Merged in source order, the same as a serial run
+        for ((((path, function), (helpers, _, hash)), cached), analyzed) in handlers.into_iter().zip(inlined).zip(cached).zip(analyzed) {
+            if cached.is_none() {
+                progress.log(&function.name);
+            }
+            let Some(details) = cached.or(analyzed) else {
+                continue;
+            };
+            cache.store_handler(hash, &details);
+
+            index.handlers.entry(function.name.clone()).or_default().push(HandlerDef {
+                file: path.clone(),
+                line: function.line,
+                details,
+                helpers,
+            });
+        }
+        index.files = queue;
+        Ok(index)
+    }
+
+    pub fn get(&self, name: &str) -> &[HandlerDef]
+    {
+        self.handlers.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    // With duplicate definitions, gsc_<x>.cpp for a gsc_<x>_... handler wins, otherwise the first one found
+    pub fn resolve(&self, name: &str) -> Option<&HandlerDef>
+    {
+        let definitions = self.get(name);
+        definitions.iter()
+            .find(|d| {
+                d.file.file_stem()
+                    .and_then(|s| s.to_str())
+                    .is_some_and(|stem| name.starts_with(&format!("{}_", stem)))
+            })
+            .or(definitions.first())
+    }
+
+    pub fn handlers(&self) -> impl Iterator<Item = (&str, &[HandlerDef])>
+    {
+        self.handlers.iter().map(|(name, defs)| (name.as_str(), defs.as_slice()))
+    }
+
+    pub fn handler_count(&self) -> usize
+    {
+        self.handlers.len()
+    }
+
+    pub fn file_count(&self) -> usize
+    {
+        self.files.len()
+    }
+}
+
+// Copies the body of each called helper in front of the statement calling it, so that
+//     if (!get_args(&a)) return;
+// reads as { ...get_args body... } if (!get_args(&a)) return;
+// calling holds the functions being inlined, to stop at recursion and MAX_HELPER_DEPTH
+fn inline_helpers(body: &str, bodies: &HashMap<&str, &str>, calling: &mut Vec<String>, helpers: &mut Vec<String>) -> String
+{
+    let tokens: Vec<Token> = cpp_lexer::tokenize(body)
+        .into_iter()
+        .filter(|t| t.kind != TokenKind::Comment)
+        .collect();
+
+    let mut inlined = String::new();
+    let mut pos = 0;
+    let mut statement_start = 0;
+    let mut header_ends: HashSet<usize> = HashSet::new();
+    for (i, token) in tokens.iter().enumerate() {
+        // A statement starts after ; { } else and the ) closing an if/for/while header
+        if token.is(";") || token.is("{") || token.is("}") || token.is("else") || header_ends.contains(&i) {
+            statement_start = token.end();
+            continue;
+        }
+        if !tokens.get(i + 1).is_some_and(|t| t.is("(")) {
+            continue;
+        }
+        if token.is("if") || token.is("for") || token.is("while") || token.is("switch") {
+            header_ends.extend(cpp_lexer::matching_close(&tokens, i + 1));
+            continue;
+        }
+        // The lexer gives -> and :: as two one-character tokens
+        let is_member = i > 0 && (tokens[i - 1].is(".")
+            || (i > 1 && tokens[i - 2].is("-") && tokens[i - 1].is(">"))
+            || (i > 1 && tokens[i - 2].is(":") && tokens[i - 1].is(":")));
+        let Some(helper) = bodies.get(token.text) else {
+            continue;
+        };
+        if token.kind != TokenKind::Ident || is_member || calling.len() > MAX_HELPER_DEPTH
+            || calling.iter().any(|c| c == token.text) || API_PREFIXES.iter().any(|p| token.text.starts_with(p)) {
+            continue;
+        }
+
+        if !helpers.iter().any(|h| h == token.text) {
+            helpers.push(token.text.to_string());
+        }
+        calling.push(token.text.to_string());
+        let helper_body = inline_helpers(helper, bodies, calling, helpers);
+        calling.pop();
+
+        inlined.push_str(&body[pos..statement_start]);
+        inlined.push_str(&format!("{{{}}}\n", helper_body));
+        pos = statement_start;
+    }
+    inlined.push_str(&body[pos..]);
+    inlined
+}
+
+// f over every item, spread over the available cores, results in the order of the items
+// A panic is caught per item and returned as its message, so one bad file can't take the
+// whole run down and its problem is reported with the file it came from
+fn parallel_map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<std::result::Result<R, String>>
+{
+    let threads = thread::available_parallelism().map_or(1, |n| n.get()).min(items.len()).max(1);
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, std::result::Result<R, String>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| scope.spawn(|| {
+                let mut done = Vec::new();
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(i) else {
+                        break;
+                    };
+                    done.push((i, panic::catch_unwind(AssertUnwindSafe(|| f(item))).map_err(panic_message)));
+                }
+                done
+            }))
+            .collect();
+        workers.into_iter()
+            .flat_map(|worker| worker.join().unwrap_or_default())
+            .collect()
+    });
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, r)| r).collect()
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String
+{
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload.downcast_ref::<&str>().map_or("unknown panic", |m| m).to_string(),
+    }
+}
+
+fn internal_error(path: &Path, line: Option<usize>, message: String) -> Error
+{
+    Error::Parse {
+        file: path.to_path_buf(),
+        line,
+        message: format!("internal error while reading the source, please report it: {}", message),
+    }
+}
+
+fn collect_source_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()>
+{
+    for entry in fs::read_dir(dir).map_err(Error::io(dir))? {
+        let path = entry.map_err(Error::io(dir))?.path();
+        if path.is_dir() {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            if name.starts_with('.') || SKIPPED_DIRS.contains(&name) {
+                continue;
+            }
+            collect_source_files(&path, files)?;
+        } else if path.extension().and_then(|e| e.to_str()).is_some_and(|e| SOURCE_EXTENSIONS.contains(&e)) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fresh directory with the given files, removed again by the caller
+    fn source_tree(name: &str, files: &[(&str, &[u8])]) -> PathBuf
+    {
+        let dir = std::env::temp_dir().join(format!("ScriptDocTool-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        for (file, content) in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    fn build(src_dir: &Path) -> SourceIndex
+    {
+        let types = TypeTables::preset("t1x").unwrap();
+        let mut cache = Cache::load(None, types.fingerprint());
+        SourceIndex::build(src_dir, &types, &mut cache, &Progress::new(false)).unwrap()
+    }
+
+    #[test]
+    fn duplicate_definitions_prefer_the_matching_file()
+    {
+        let dir = source_tree("resolve", &[
+            ("gsc/custom/weapons_impl.cpp", b"void gsc_utils_sqrt() { Scr_AddInt(0); }\n"),
+            ("gsc/gsc_utils.cpp", b"\nvoid gsc_utils_sqrt() { Scr_AddFloat(1.0); }\n"),
+            ("gsc/gsc_extra.cpp", b"void gsc_extra_ping() {}\n"),
+        ]);
+        let index = build(&dir.join("gsc"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(index.get("gsc_utils_sqrt").len(), 2);
+        let sqrt = index.resolve("gsc_utils_sqrt").unwrap();
+        assert!(sqrt.file.ends_with("gsc_utils.cpp"));
+        assert_eq!(sqrt.line, 2);
+        assert!(index.resolve("gsc_extra_ping").is_some());
+        assert!(index.resolve("gsc_utils_missing").is_none());
+    }
+
+    #[test]
+    fn included_headers_and_latin1_sources()
+    {
+        let dir = source_tree("headers", &[
+            ("gsc/gsc_utils.cpp", b"#include \"../shared/helpers.h\"\n// caf\xe9\nvoid gsc_utils_name() { Scr_AddString(\"\xe9\"); }\n"),
+            ("shared/helpers.h", b"void gsc_shared_helper() {}\n"),
+        ]);
+        let index = build(&dir.join("gsc"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(index.file_count(), 2);
+        assert!(index.resolve("gsc_shared_helper").is_some_and(|d| d.file.ends_with("helpers.h")));
+        assert_eq!(index.resolve("gsc_utils_name").unwrap().details.returns, ["string"]);
+    }
+
+    fn inline(body: &str, bodies: &[(&str, &str)]) -> (String, Vec<String>)
+    {
+        let bodies: HashMap<&str, &str> = bodies.iter().copied().collect();
+        let mut helpers: Vec<String> = Vec::new();
+        let body = inline_helpers(body, &bodies, &mut vec!["gsc_handler".to_string()], &mut helpers);
+        (body, helpers)
+    }
+
+    #[test]
+    fn helpers_are_inlined_before_the_calling_statement()
+    {
+        let (body, helpers) = inline("if (!get_args(&a)) return;\nScr_AddInt(a);", &[("get_args", "stackGetParams(\"i\", a);")]);
+        assert_eq!(helpers, ["get_args"]);
+        assert!(body.starts_with("{stackGetParams(\"i\", a);}\nif (!get_args(&a))"));
+        // Stack API calls and member calls are never inlined
+        let (_, helpers) = inline("Scr_AddInt(1); obj.get_args(); ptr->get_args(); Object::get_args();", &[("get_args", "x();"), ("Scr_AddInt", "y();")]);
+        assert!(helpers.is_empty());
+    }
+
+    #[test]
+    fn helpers_stop_at_the_depth_limit_and_recursion()
+    {
+        let chain = [("h1", "h2();"), ("h2", "h3();"), ("h3", "h4();"), ("h4", "h5();"), ("h5", "Scr_AddInt(5);")];
+        let (body, helpers) = inline("h1();", &chain);
+        assert_eq!(helpers, ["h1", "h2", "h3"]);
+        assert!(!body.contains("Scr_AddInt"));
+
+        let (body, helpers) = inline("a();", &[("a", "b(); Scr_AddInt(1);"), ("b", "a(); gsc_handler();")]);
+        assert_eq!(helpers, ["a", "b"]);
+        assert_eq!(body.matches("Scr_AddInt").count(), 1);
+    }
+
+    #[test]
+    fn parallel_map_keeps_the_item_order()
+    {
+        let items: Vec<u64> = (0..500).collect();
+        // Uneven work so the workers finish out of order
+        let results = parallel_map(&items, |n| {
+            std::thread::sleep(std::time::Duration::from_micros((500 - n) % 7 * 50));
+            n * 2
+        });
+        assert_eq!(results, items.iter().map(|n| Ok(n * 2)).collect::<Vec<_>>());
+        assert!(parallel_map(&[] as &[u64], |n| *n).is_empty());
+    }
+
+    #[test]
+    fn parallel_map_returns_panics_per_item()
+    {
+        let results = parallel_map(&[1, 0, 2], |n| {
+            assert!(*n != 0, "zero");
+            *n
+        });
+        assert_eq!(results, [Ok(1), Err("zero".to_string()), Ok(2)]);
+    }
+}
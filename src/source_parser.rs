@@ -1,135 +1,261 @@
 // Regex patterns are AI generated
 
 use regex::Regex;
-use serde::Serialize;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
 use std::fs::read_to_string;
-use std::{io, io::Write};
-use termsize;
+use std::path::{Path, PathBuf};
 
+use crate::arity;
+use crate::cache::Cache;
+use crate::config::Config;
+use crate::cpp_lexer::{self, Token, TokenKind};
+use crate::diagnostics::Diagnostics;
+use crate::error::{Error, Result};
+use crate::filter::{CategoryRules, Filters};
+use crate::progress::Progress;
+use crate::returns;
+use crate::source_index::SourceIndex;
+use crate::types::TypeTables;
 use crate::{CLEAR_COLOR, BHI_WHITE, B_YELLOW, B_CYAN};
 
 #[derive(Serialize, Debug)]
-pub(crate) struct ParseResult {
+pub struct ParseResult {
     // BTreeMap arranges entries in alphabetical order
     pub functions: BTreeMap<String, BTreeMap<String, ScriptFunction>>,
     pub methods: BTreeMap<String, BTreeMap<String, ScriptFunction>>,
+    // Problems that didn't stop the parse, e.g. a handler defined twice
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    // Registry entries without a handler definition as (script name, handler), they are still documented
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub undefined: Vec<(String, String)>,
+    // gsc_ handlers in gsc_*.cpp files that gsc.cpp never registers as (handler, file)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unregistered: Vec<(String, String)>,
+    // Script names dropped by each filter rule
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub excluded: BTreeMap<String, Vec<String>>,
+    // How each ParseOptions.explain name was resolved, filtered or lost
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub explain: BTreeMap<String, Vec<String>>,
+    // The tables the types were inferred with, the doc generator names types with them
+    #[serde(skip)]
+    pub types: TypeTables,
 }
 
-#[derive(Serialize, Debug)]
-pub(crate) struct ScriptFunction {
+#[derive(Serialize, Debug, Default)]
+pub struct ScriptFunction {
     #[serde(skip_serializing)]
     pub name: String,
     #[serde(rename = "scriptName")]
     pub script_name: String,
     pub params: Option<Vec<ScriptParameter>>,
+    // Alternative signatures, from stackGetParams calls in other branches
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub overloads: Vec<Vec<ScriptParameter>>,
     pub returns: Vec<String>,   // e.g., ["bool", "undefined"]
+    // Comment above the handler definition, without its @param tags
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    // What a method is called on, e.g. "player" or "hud element"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub receiver: Option<String>,
+    // Messages the handler reports on misuse, e.g. "argument is undefined or has a wrong type"
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    // Explains returns that only happen on some paths, e.g. "undefined" -> "if the arguments are invalid"
+    #[serde(rename = "returnConditions", skip_serializing_if = "BTreeMap::is_empty")]
+    pub return_conditions: BTreeMap<String, String>,
+    // Non-zero third field of the registry entry, only available in developer mode
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub developer: bool,
+    // The registry entry in gsc.cpp and the handler definition
+    #[serde(rename = "registeredAt", skip_serializing_if = "Option::is_none")]
+    pub registered_at: Option<SourceLocation>,
+    #[serde(rename = "definedAt", skip_serializing_if = "Option::is_none")]
+    pub defined_at: Option<SourceLocation>,
+    // Preprocessor condition around the registry entry, e.g. "COMPILE_SQLITE"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
 }
 
 #[derive(Serialize, Debug, Clone)]
-pub(crate) struct ScriptParameter {
+pub struct SourceLocation {
+    pub file: String,   // relative to the server directory, e.g. src/gsc/gsc_utils.cpp
+    pub line: usize,
+}
+
+impl SourceLocation {
+    // file is made relative to root
+    pub fn new(root: &Path, file: &Path, line: usize) -> SourceLocation
+    {
+        let file = file.strip_prefix(root).unwrap_or(file);
+        let file = file.strip_prefix(".").unwrap_or(file);
+        SourceLocation {
+            file: file.to_string_lossy().replace('\\', "/"),
+            line,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ScriptParameter {
     pub param_type: String,
     pub param_name: String,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub optional: bool,
+    // Stands for any number of trailing arguments
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub variadic: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    // From an @param tag in the comment above the handler
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
 }
 
-#[derive(Debug)]
+// Also what the cache keeps per handler
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub(crate) struct ScriptFunctionDetails {
     pub params: Option<Vec<ScriptParameter>>,
+    pub overloads: Vec<Vec<ScriptParameter>>,
     pub returns: Vec<String>,
+    pub return_conditions: BTreeMap<String, String>,
+    pub errors: Vec<String>,
+    pub description: Option<String>,
+    pub receiver: Option<String>,
 }
 
 /*fn print_type_of<T>(_: &T) {
     println!("{}", std::any::type_name::<T>());
 }*/
 
-pub(crate) fn parse() -> io::Result<ParseResult>
-{
-    // Parse gsc.cpp to get script names and function names
-    println!("{BHI_WHITE}Reading {B_CYAN}gsc.cpp{CLEAR_COLOR}");
-    let (script_functions, script_methods) = parse_gsc_cpp("src/gsc/gsc.cpp")?;
-
-    // Combine with function details from gsc_{category}.cpp files
-    let mut final_functions: BTreeMap<String, BTreeMap<String, ScriptFunction>> = BTreeMap::new();
-    let mut final_methods: BTreeMap<String, BTreeMap<String, ScriptFunction>> = BTreeMap::new();
-
-    println!("{BHI_WHITE}Parsing Script Functions{CLEAR_COLOR}");
-    for (category, functions) in script_functions {
-        //println!("Category: {}", &category);
-        //println!("Functions: {:#?}", functions);
-        //std::process::exit(0);
-        let category_file = format!("src/gsc/gsc_{}.cpp", category);
-        let function_details = parse_category_file(&category_file)?;
-
-        for func in functions {
-            if let Some(details) = function_details.get(&func.name) {
-                let script_func = ScriptFunction {
-                    name: func.name.clone(),
-                    script_name: func.script_name.clone(),
-                    params: Some(details.params.clone().expect("failed to get params")),
-                    returns: details.returns.clone(),
-                };
-                final_functions
-                    .entry(category.clone()).or_default()
-                    .insert(func.name.clone(), script_func);
+pub struct ParseOptions {
+    pub filters: Filters,
+    pub categories: CategoryRules,
+    pub types: TypeTables,
+    // Script or handler names to print a resolution trace for
+    pub explain: Vec<String>,
+    // Server directory the layout paths are relative to
+    pub root: PathBuf,
+    // Reuse results for unchanged files from this file, e.g. root/.ScriptDocTool.cache.
+    // None (the default) parses everything and writes nothing
+    pub cache_file: Option<PathBuf>,
+    // Print progress, counts and the diagnostics report to stdout
+    pub verbose: bool,
+}
+
+impl ParseOptions {
+    // Filters, categories and types from the config, no cache
+    pub fn new(root: &Path, config: &Config, type_preset: &str) -> Result<ParseOptions>
+    {
+        let filters = Filters::from_config(config);
+        let categories = CategoryRules::from_config(config);
+        let types = TypeTables::from_config(config, type_preset);
+        match (filters, categories, types) {
+            (Ok(filters), Ok(categories), Ok(types)) => Ok(ParseOptions {
+                filters,
+                categories,
+                types,
+                explain: Vec::new(),
+                root: root.to_path_buf(),
+                cache_file: None,
+                verbose: false,
+            }),
+            (filters, categories, types) => {
+                Err(Error::many([filters.err(), categories.err(), types.err()].into_iter().flatten().collect()))
             }
         }
     }
-    let termsize::Size {cols, ..} = termsize::get().unwrap();
-    let mut outputstr = String::new();
-    for _ in 0..cols {
-        outputstr.push(' ');
-    }
-    print!("\r{}", outputstr);
+}
 
-    let mut total_funcs: u16 = 0;
-    for (category, funcs) in &final_functions {
-        println!("{B_YELLOW}{}{CLEAR_COLOR} functions in {B_CYAN}gsc_{}.cpp{CLEAR_COLOR}", funcs.len(), category);
-        total_funcs = total_funcs
-            .checked_add(funcs.len() as u16)
-            .expect("too many functions to fit in u16");
-    }
-    println!("Total {B_YELLOW}{}{CLEAR_COLOR} script functions", total_funcs);
+// Where a codebase registers its builtins and defines their handlers
+#[derive(Debug)]
+pub struct RegistryLayout {
+    pub registry: PathBuf,          // e.g. src/gsc/gsc.cpp
+    pub src_dir: PathBuf,           // indexed for handler definitions
+    pub function_array: String,     // e.g. scriptFunctions
+    pub method_array: String,
+}
 
-    //println!("script_methods @68: {:#?}", script_methods);
-    println!("\n{BHI_WHITE}Parsing Script Methods{CLEAR_COLOR}");
-    for (category, methods) in script_methods {
-        //println!("category @70: {}", &category);
+pub(crate) fn parse(layout: &RegistryLayout, options: &ParseOptions) -> Result<ParseResult>
+{
+    let layout = RegistryLayout {
+        registry: options.root.join(&layout.registry),
+        src_dir: options.root.join(&layout.src_dir),
+        function_array: layout.function_array.clone(),
+        method_array: layout.method_array.clone(),
+    };
 
-        let category_file = format!("src/gsc/gsc_{}.cpp", category);
-        //println!("category_file @73: {}", &category_file);
-        let function_details = parse_category_file(&category_file)?;
+    let verbose = options.verbose;
+    let mut diagnostics = Diagnostics::new(&options.explain);
 
-        //println!("function_details @75: {:#?}", function_details);
+    // Parse gsc.cpp to get script names and function names
+    if verbose {
+        println!("{BHI_WHITE}Reading {B_CYAN}{}{CLEAR_COLOR}", layout.registry.display());
+    }
+    let Registry {
+        functions: script_functions,
+        methods: script_methods,
+        skipped,
+    } = parse_gsc_cpp(&layout, &options.root)?;
+    for warning in skipped {
+        if verbose {
+            println!("{B_YELLOW}Skipped{CLEAR_COLOR} {}", warning);
+        }
+        diagnostics.warnings.push(warning);
+    }
 
-        for meth in methods {
-            if let Some(details) = function_details.get(&meth.name) {
-                let script_meth = ScriptFunction {
-                    name: meth.name.clone(),
-                    script_name: meth.script_name.clone(),
-                    params: Some(details.params.clone().expect("failed to get params")),
-                    returns: details.returns.clone(),
-                };
-                final_methods
-                    .entry(category.clone()).or_default()
-                    .insert(meth.script_name.clone(), script_meth);
-            }
+    // Find where every handler is defined
+    if verbose {
+        println!("{BHI_WHITE}Indexing handlers in {B_CYAN}{}{CLEAR_COLOR}", layout.src_dir.display());
+    }
+    let mut cache = Cache::load(options.cache_file.as_deref(), options.types.fingerprint());
+    let progress = Progress::new(verbose);
+    let index = SourceIndex::build(&layout.src_dir, &options.types, &mut cache, &progress)?;
+    progress.finish();
+    // Failing to write the cache only makes the next run slower
+    if let Err(e) = cache.save() {
+        if verbose {
+            println!("{B_YELLOW}Could not write the cache{CLEAR_COLOR}: {}", e);
         }
+        diagnostics.warnings.push(format!("Could not write the cache: {}", e));
     }
-    let termsize::Size {cols, ..} = termsize::get().unwrap();
-    let mut outputstr = String::new();
-    for _ in 0..cols {
-        outputstr.push(' ');
+    if verbose {
+        println!("{B_YELLOW}{}{CLEAR_COLOR} handlers in {B_YELLOW}{}{CLEAR_COLOR} files", index.handler_count(), index.file_count());
+        if cache.file_hits > 0 || cache.handler_hits > 0 {
+            println!("{B_YELLOW}{}{CLEAR_COLOR} files and {B_YELLOW}{}{CLEAR_COLOR} handlers unchanged since the last run", cache.file_hits, cache.handler_hits);
+        }
+        println!("\n{BHI_WHITE}Parsing Script Functions{CLEAR_COLOR}");
     }
-    print!("\r{}", outputstr);
+    let final_functions = resolve_entries(script_functions, &index, options, &mut diagnostics, false);
 
-    let mut total_meths: u16 = 0;
-    for (category, meths) in &final_methods {
-        println!("{B_YELLOW}{}{CLEAR_COLOR} methods in {B_CYAN}gsc_{}.cpp{CLEAR_COLOR}", meths.len(), category);
-        total_meths = total_meths
-            .checked_add(meths.len() as u16)
-            .expect("too many functions to fit in u16");
+    if verbose {
+        let mut total_funcs: usize = 0;
+        for (category, funcs) in &final_functions {
+            println!("{B_YELLOW}{}{CLEAR_COLOR} functions in {B_CYAN}{}{CLEAR_COLOR}", funcs.len(), category);
+            total_funcs += funcs.len();
+        }
+        println!("Total {B_YELLOW}{}{CLEAR_COLOR} script functions", total_funcs);
+
+        println!("\n{BHI_WHITE}Parsing Script Methods{CLEAR_COLOR}");
+    }
+    let final_methods = resolve_entries(script_methods, &index, options, &mut diagnostics, true);
+    diagnostics.finish(&index);
+
+    if verbose {
+        let mut total_meths: usize = 0;
+        for (category, meths) in &final_methods {
+            println!("{B_YELLOW}{}{CLEAR_COLOR} methods in {B_CYAN}{}{CLEAR_COLOR}", meths.len(), category);
+            total_meths += meths.len();
+        }
+        println!("Total {B_YELLOW}{}{CLEAR_COLOR} script methods\n", total_meths);
+
+        diagnostics.report(&index);
     }
-    println!("Total {B_YELLOW}{}{CLEAR_COLOR} script methods\n", total_meths);
 
     // Output as JSON
     //print_type_of(&final_data);
@@ -137,182 +263,1090 @@ pub(crate) fn parse() -> io::Result<ParseResult>
     Ok(ParseResult {
         functions: final_functions,
         methods: final_methods,
+        warnings: diagnostics.warnings,
+        undefined: diagnostics.undefined,
+        unregistered: diagnostics.unregistered,
+        excluded: diagnostics.excluded,
+        explain: diagnostics.traces,
+        types: options.types.clone(),
     })
 }
 
-// Parse gsc.cpp to extract script names and function names
-fn parse_gsc_cpp(file_path: &str) -> io::Result<(
-    HashMap<String, Vec<ScriptFunction>>,
-    HashMap<String, Vec<ScriptFunction>>,
-)>
+// Attach the handler details to registry entries and group them by doc category
+fn resolve_entries(
+    entries: Vec<ScriptFunction>,
+    index: &SourceIndex,
+    options: &ParseOptions,
+    diagnostics: &mut Diagnostics,
+    is_method: bool,
+) -> BTreeMap<String, BTreeMap<String, ScriptFunction>>
 {
-    let code = read_to_string(file_path).expect("Failed to read gsc.cpp");
-    let mut functions: HashMap<String, Vec<ScriptFunction>> = HashMap::new();
-    let mut methods: HashMap<String, Vec<ScriptFunction>> = HashMap::new();
-    let mut current = None;
-
-    let line_re = Regex::new(r#"\{\s*"([^"]+)"\s*,\s*(\w+)\s*,\s*\d+\s*\},"#).unwrap();
-
-    for line in code.lines() {
-        let sline = line.trim();
-        if sline.starts_with("//") { continue; }
-        if sline.contains("scriptFunctions[]") {
-            current = Some("functions");
+    let mut resolved: BTreeMap<String, BTreeMap<String, ScriptFunction>> = BTreeMap::new();
+    let kind = if is_method { "method" } else { "function" };
+
+    for mut entry in entries {
+        diagnostics.registered.insert(entry.name.clone());
+        let registry = entry.registered_at.as_ref().map_or("gsc.cpp".to_string(), |at| format!("{}:{}", at.file, at.line));
+        let mut step = format!("registered in {} as {} \"{}\" with handler {}", registry, kind, &entry.script_name, &entry.name);
+        if let Some(condition) = &entry.condition {
+            step.push_str(&format!(" under #if {}", condition));
+        }
+        diagnostics.trace(&entry, step);
+
+        let definition = index.resolve(&entry.name);
+        let category = options.categories.category_for(&entry, definition.map(|d| d.file.as_path()));
+        if let Some(rule) = options.filters.check(&entry, &category) {
+            diagnostics.trace(&entry, format!("filtered out: {}", &rule));
+            diagnostics.excluded.entry(rule).or_default().push(entry.script_name);
             continue;
-        } else if sline.contains("scriptMethods[]") {
-            current = Some("methods");
-            //println!("current: methods");
+        }
+
+        let definitions = index.get(&entry.name);
+        if let Some(definition) = definition && definitions.len() > 1 {
+            let files: Vec<String> = definitions.iter().map(|d| d.file.display().to_string()).collect();
+            if options.verbose {
+                println!(
+                    "{B_YELLOW}Ambiguous{CLEAR_COLOR} {} is defined in {}, using {B_CYAN}{}{CLEAR_COLOR}",
+                    &entry.name, files.join(", "), definition.file.display()
+                );
+            }
+            diagnostics.warnings.push(format!("{} is defined in {}, using {}", &entry.name, files.join(", "), definition.file.display()));
+            diagnostics.trace(&entry, format!("defined more than once: {}", files.join(", ")));
+        }
+
+        match definition {
+            Some(definition) => {
+                diagnostics.trace(&entry, format!("definition used from {}:{}", definition.file.display(), definition.line));
+                entry.defined_at = Some(SourceLocation::new(&options.root, &definition.file, definition.line));
+                if !definition.helpers.is_empty() {
+                    diagnostics.trace(&entry, format!("followed calls into {}", definition.helpers.join(", ")));
+                }
+                entry.params = definition.details.params.clone();
+                entry.overloads = definition.details.overloads.clone();
+                entry.returns = definition.details.returns.clone();
+                entry.return_conditions = definition.details.return_conditions.clone();
+                entry.errors = definition.details.errors.clone();
+                entry.description = definition.details.description.clone();
+                if is_method {
+                    entry.receiver = definition.details.receiver.clone();
+                }
+            }
+            None => {
+                // Still documented, there just is nothing to infer
+                diagnostics.trace(&entry, format!("no definition of {} in {} indexed files", &entry.name, index.file_count()));
+                diagnostics.undefined.push((entry.script_name.clone(), entry.name.clone()));
+                entry.returns = vec!["unknown".to_string()];
+            }
+        }
+        diagnostics.trace(&entry, format!("documented in {}s/{}.rst", kind, &category));
+
+        let key = if is_method { entry.script_name.clone() } else { entry.name.clone() };
+        resolved
+            .entry(category).or_default()
+            .insert(key, entry);
+    }
+    resolved
+}
+
+struct Registry {
+    functions: Vec<ScriptFunction>,
+    methods: Vec<ScriptFunction>,
+    // Entries that have a name but can't be read, as "file:line: message"
+    skipped: Vec<String>,
+}
+
+// Parse gsc.cpp to extract script names and function names
+fn parse_gsc_cpp(layout: &RegistryLayout, root: &Path) -> Result<Registry>
+{
+    let code = read_to_string(&layout.registry).map_err(Error::io(&layout.registry))?;
+    let mut functions: Vec<ScriptFunction> = Vec::new();
+    let mut methods: Vec<ScriptFunction> = Vec::new();
+    let mut errors: Vec<Error> = Vec::new();
+    let mut skipped: Vec<String> = Vec::new();
+    let mut arrays_found = 0;
+    let parse_error = |line: usize, message: String| Error::Parse { file: layout.registry.clone(), line: Some(line), message };
+
+    // Keep the active #if/#ifdef condition next to every token
+    let mut conditions = ConditionStack::default();
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut guards: Vec<Option<String>> = Vec::new();
+    for token in cpp_lexer::tokenize(&code) {
+        match token.kind {
+            TokenKind::Comment => {}
+            TokenKind::Directive => conditions.apply(&token),
+            _ => {
+                tokens.push(token);
+                guards.push(conditions.current());
+            }
+        }
+    }
+
+    let mut i = 0;
+    while i < tokens.len() {
+        // Only look at the array definitions, e.g. scriptFunctions[] = { ... };
+        let current = match tokens[i].text {
+            name if name == layout.function_array => "functions",
+            name if name == layout.method_array => "methods",
+            _ => { i += 1; continue; }
+        };
+        i += 1;
+        if !(tokens.get(i).is_some_and(|t| t.is("[")) && tokens.get(i + 1).is_some_and(|t| t.is("]"))) {
             continue;
         }
-        if current.is_none() || sline.contains("test") {
+        while i < tokens.len() && !tokens[i].is("{") && !tokens[i].is(";") {
+            i += 1;
+        }
+        if i >= tokens.len() || tokens[i].is(";") {
             continue;
         }
-        if let Some(caps) = line_re.captures(sline) {
-            let script_name = caps[1].to_string();
-            let func_name = caps[2].to_string();
-            let parts: Vec<&str> = func_name.split('_').collect();
-            if parts.len() >= 2 && parts[0] == "gsc" {
-                let category = parts[1].to_string();
-                let script_func = ScriptFunction {
-                    name: func_name,
-                    script_name,
-                    params: None,
-                    returns: vec![],
-                };
+        let array_line = cpp_lexer::line_at(&code, tokens[i].start);
+        arrays_found += 1;
+        i += 1;
+
+        // Inside the array, every entry is a braced list of fields
+        while i < tokens.len() && !tokens[i].is("}") {
+            if !tokens[i].is("{") {
+                i += 1;
+                continue;
+            }
+            let condition = guards[i].clone();
+            let line = cpp_lexer::line_at(&code, tokens[i].start);
+            let entry_start = tokens[i].start;
+            i += 1;
+            let mut fields: Vec<Vec<Token>> = Vec::new();
+            let mut field: Vec<Token> = Vec::new();
+            while i < tokens.len() && !tokens[i].is("}") {
+                if tokens[i].is(",") {
+                    fields.push(std::mem::take(&mut field));
+                } else {
+                    field.push(tokens[i]);
+                }
+                i += 1;
+            }
+            fields.push(field);
+            if i >= tokens.len() {
+                errors.push(parse_error(line, "registry entry is never closed".to_string()));
+                break;
+            }
+            i += 1;
+
+            let script_func = registry_entry(&fields);
+            // A named entry that isn't {"name", handler, developer}, terminators like {NULL, NULL, 0} have no name
+            if script_func.is_none() && fields[0].first().is_some_and(|t| t.kind == TokenKind::Str) {
+                let text = code[entry_start..tokens[i - 1].end()].split_whitespace().collect::<Vec<_>>().join(" ");
+                skipped.push(format!("{}:{}: malformed registry entry {}, expected {{\"name\", handler, developer}}", layout.registry.display(), line, text));
+                continue;
+            }
+            if let Some(mut script_func) = script_func {
+                // Never compiled
+                if condition.as_deref() == Some("0") {
+                    continue;
+                }
+                script_func.condition = condition;
+                script_func.registered_at = Some(SourceLocation::new(root, &layout.registry, line));
                 match current {
-                    Some("functions") => functions.entry(category).or_default().push(script_func),
-                    Some("methods") => methods.entry(category).or_default().push(script_func),
+                    "functions" => functions.push(script_func),
+                    "methods" => methods.push(script_func),
                     _ => {}
                 }
             }
         }
+        if i >= tokens.len() {
+            errors.push(parse_error(array_line, "registry array is never closed".to_string()));
+        }
+    }
+
+    if arrays_found == 0 {
+        errors.push(Error::Parse {
+            file: layout.registry.clone(),
+            line: None,
+            message: format!("no {}[] or {}[] array found", layout.function_array, layout.method_array),
+        });
+    }
+    if !errors.is_empty() {
+        return Err(Error::many(errors));
+    }
+    Ok(Registry { functions, methods, skipped })
+}
+
+// {"name", handler, developer} -> ScriptFunction, anything else (e.g. the {NULL, NULL, 0} terminator) is skipped
+fn registry_entry(fields: &[Vec<Token>]) -> Option<ScriptFunction>
+{
+    match fields {
+        [name, handler, ..] if name.len() == 1 && name[0].kind == TokenKind::Str => {
+            let handler = handler_name(handler)?;
+            // 0, qfalse or missing for builtins available outside developer mode
+            let developer = fields.get(2).is_some_and(|flag| {
+                !flag.is_empty() && !flag.iter().all(|t| t.is("0") || t.is("qfalse") || t.is("false"))
+            });
+            Some(ScriptFunction {
+                name: handler.to_string(),
+                script_name: name[0].str_value().to_string(),
+                params: None,
+                overloads: vec![],
+                returns: vec![],
+                errors: vec![],
+                description: None,
+                receiver: None,
+                return_conditions: BTreeMap::new(),
+                developer,
+                registered_at: None,
+                defined_at: None,
+                condition: None,
+            })
+        }
+        _ => None,
     }
-    Ok((functions, methods))
 }
 
-fn parse_category_file(file_path: &str) -> io::Result<HashMap<String, ScriptFunctionDetails>>
+// The handler is the last identifier, so (xfunction_t)gsc_utils_opt and &gsc_utils_opt read as gsc_utils_opt
+fn handler_name<'a>(field: &[Token<'a>]) -> Option<&'a str>
 {
-    let code = read_to_string(file_path).expect("Failed to read category file");
-    let mut details_map: HashMap<String, ScriptFunctionDetails> = HashMap::new();
-
-    let func_signature_re = Regex::new(r"(?m)void\s+(\w+)\s*\([^)]*\)\s*(?://[^\n]*)?\s*\{").unwrap();
-    //println!("parse_category_file: {}", file_path.to_string());
-
-    for cap in func_signature_re.captures_iter(&code) {
-        let function_name = cap[1].to_string();
-        let start = cap.get(0).unwrap().end();
-        let mut brace_count = 1;
-        let mut body = String::new();
-        let mut i = start;
-
-        let termsize::Size {cols, ..} = termsize::get().unwrap();
-        let numspaces: u16 = (cols - function_name.chars().count() as u16) - 3 as u16;
-        let mut outputstr = format!("\r{}...", &function_name);
-        for _ in 1..numspaces {
-            outputstr.push(' ');
-        }
-        print!("{}", outputstr);
-        io::stdout().flush().unwrap();
-        std::thread::sleep(std::time::Duration::from_millis(20));
-
-        while i < code.len() && brace_count > 0 {
-            let c = code.as_bytes()[i] as char;
-            body.push(c);
-            if c == '{' {
-                brace_count += 1;
-            } else if c == '}' {
-                brace_count -= 1;
+    if field.iter().any(|t| !matches!(t.kind, TokenKind::Ident | TokenKind::Punct)) {
+        return None;
+    }
+    field.iter().rev().find(|t| t.kind == TokenKind::Ident).map(|t| t.text)
+}
+
+// Nested #if/#elif/#else blocks, each frame holds the conditions of its branches so far
+#[derive(Default)]
+struct ConditionStack {
+    frames: Vec<Vec<String>>,
+    active: Vec<String>,
+}
+
+impl ConditionStack {
+    fn apply(&mut self, directive: &Token)
+    {
+        let (name, rest) = cpp_lexer::directive_parts(directive);
+        match name {
+            "if" => self.push(normalize_condition(rest)),
+            "ifdef" => self.push(rest.to_string()),
+            "ifndef" => self.push(negate_condition(rest)),
+            "elif" | "else" => {
+                let (Some(branches), Some(active)) = (self.frames.last_mut(), self.active.last_mut()) else {
+                    return;
+                };
+                let mut parts: Vec<String> = branches.iter().map(|c| negate_condition(c)).collect();
+                if name == "elif" {
+                    let condition = normalize_condition(rest);
+                    parts.push(condition.clone());
+                    branches.push(condition);
+                }
+                *active = parts.join(" && ");
+            }
+            "endif" => {
+                self.frames.pop();
+                self.active.pop();
             }
+            _ => {}
+        }
+    }
+
+    fn push(&mut self, condition: String)
+    {
+        self.frames.push(vec![condition.clone()]);
+        self.active.push(condition);
+    }
+
+    fn current(&self) -> Option<String>
+    {
+        if self.active.iter().any(|c| c == "0") {
+            return Some("0".to_string());
+        }
+        let active: Vec<&str> = self.active.iter()
+            .map(String::as_str)
+            .filter(|c| *c != "1")
+            .collect();
+        if active.is_empty() {
+            None
+        } else {
+            Some(active.join(" && "))
+        }
+    }
+}
+
+static CONDITION_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(!)?\s*(?:defined\s*\(\s*(\w+)\s*\)|defined\s+(\w+)|(\w+)\s*==\s*([01]))$").unwrap()
+});
+
+// "defined(X)", "X == 1" -> "X" and "!defined(X)", "X == 0" -> "!X", each part of an "&&" chain on its own
+fn normalize_condition(condition: &str) -> String
+{
+    let condition = condition.split_whitespace().collect::<Vec<_>>().join(" ").replace("\\ ", "");
+    let balanced = |s: &str| s.matches('(').count() == s.matches(')').count();
+    if condition.contains("&&") && !condition.contains("||") && condition.split("&&").all(balanced) {
+        return condition.split("&&").map(normalize_condition).collect::<Vec<_>>().join(" && ");
+    }
+    let Some(caps) = CONDITION_RE.captures(&condition) else {
+        return condition;
+    };
+    let name = caps.get(2).or(caps.get(3)).or(caps.get(4)).unwrap().as_str();
+    let negated = caps.get(1).is_some() != (caps.get(5).map(|m| m.as_str()) == Some("0"));
+    if negated {
+        format!("!{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn negate_condition(condition: &str) -> String
+{
+    let is_name = |s: &str| s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    match condition {
+        "0" => "1".to_string(),
+        "1" => "0".to_string(),
+        c if c.starts_with('!') && is_name(&c[1..]) => c[1..].to_string(),
+        c if is_name(c) => format!("!{}", c),
+        c => format!("!({})", c),
+    }
+}
+
+// A function defined in a source file, with comments blanked out of the body
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct FunctionDef {
+    pub name: String,
+    // void name(...) { ... } could be a script handler, anything else is only followed as a helper
+    pub is_handler: bool,
+    pub body: String,
+    pub line: usize,
+    // The //, /// or /** */ comment right above the definition
+    pub comment: Option<String>,
+}
+
+// Function definitions and local includes of one source file
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct SourceFile {
+    pub functions: Vec<FunctionDef>,
+    pub includes: Vec<String>,
+}
+
+// Names followed by ( ... ) { that are not function definitions
+const NOT_FUNCTIONS: &[&str] = &["if", "for", "while", "switch", "catch", "sizeof", "return"];
+
+pub(crate) fn parse_source_file(code: &str) -> SourceFile
+{
+    let mut functions: Vec<FunctionDef> = Vec::new();
+
+    let tokens = cpp_lexer::tokenize(code);
+    let includes: Vec<String> = tokens.iter()
+        .filter(|t| t.kind == TokenKind::Directive)
+        .map(cpp_lexer::directive_parts)
+        .filter(|(name, _)| *name == "include")
+        .filter_map(|(_, rest)| rest.strip_prefix('"')?.split('"').next())
+        .map(str::to_string)
+        .collect();
+    let code_tokens: Vec<Token> = tokens.iter()
+        .filter(|t| t.kind != TokenKind::Comment)
+        .copied()
+        .collect();
+
+    // Look for definitions like: void name(...) { ... } or static int name(...) { ... }
+    let mut i = 1;
+    while i + 1 < code_tokens.len() {
+        let prev = &code_tokens[i - 1];
+        if !(code_tokens[i].kind == TokenKind::Ident && code_tokens[i + 1].is("(")
+            && (prev.kind == TokenKind::Ident || prev.is("*") || prev.is("&"))
+            && !NOT_FUNCTIONS.contains(&code_tokens[i].text) && !NOT_FUNCTIONS.contains(&prev.text)) {
             i += 1;
+            continue;
         }
-        if !body.is_empty() {
-            body.pop(); // Remove closing brace
+        let function_name = code_tokens[i].text.to_string();
+        // Start of the declaration, e.g. static for: static const char *name(...)
+        let mut start = i - 1;
+        while start > 0 && (code_tokens[start - 1].kind == TokenKind::Ident || code_tokens[start - 1].is("*") || code_tokens[start - 1].is("&")) {
+            start -= 1;
+        }
+        let comment = leading_comment(code, &tokens, code_tokens[start].start);
+        let line = cpp_lexer::line_at(code, code_tokens[i].start);
+        let Some(close_paren) = cpp_lexer::matching_close(&code_tokens, i + 1) else {
+            break;
+        };
+        if !code_tokens.get(close_paren + 1).is_some_and(|t| t.is("{")) {
+            // Declaration or call, not a definition
+            i = close_paren + 1;
+            continue;
         }
+        let open_brace = close_paren + 1;
+        let Some(close_brace) = cpp_lexer::matching_close(&code_tokens, open_brace) else {
+            break;
+        };
+        i = close_brace + 1;
 
+        let body = cpp_lexer::without_comments(
+            code,
+            &tokens,
+            code_tokens[open_brace].end()..code_tokens[close_brace].start,
+        );
         //println!("body: \n{}", &body);
 
-        let params = extract_params(&body);
-        //println!("{} params: {:#?}", &function_name, params.clone().expect("failed to print params"));
-        //std::process::exit(0);
-        let returns = extract_return_types(&body)?;
-
-        details_map.insert(
-            function_name,
-            ScriptFunctionDetails {
-                params,
-                returns,
-            },
-        );
+        functions.push(FunctionDef {
+            name: function_name,
+            is_handler: prev.is("void"),
+            body,
+            line,
+            comment,
+        });
     }
-    Ok(details_map)
+    SourceFile { functions, includes }
 }
 
-// Extract parameter types from stackGetParams
-fn extract_params(body: &str) -> Option<Vec<ScriptParameter>>
+// Comment tokens directly above pos, a blank line ends the comment.
+// Only comments on lines of their own, not the trailing comment of `} // ...` or `#endif // X`
+fn leading_comment(code: &str, tokens: &[Token], pos: usize) -> Option<String>
 {
-    let params_re = Regex::new(r#"\s*stackGetParams\(\s*"([^"]+)"\s*,([^)]+)\)"#).unwrap();
-    if let Some(caps) = params_re.captures(body) {
-        let param_types: Vec<String> = caps[1].chars().map(|s| {
-            match s {
-                'i' => "int".to_string(),
-                'v' => "vector".to_string(),
-                'f' => "float".to_string(),
-                's' => "string".to_string(),
-                'c' => "const string".to_string(),
-                'l' => "localized string".to_string(),
-                _ => "unknown".to_string(),
+    let mut end = pos;
+    let mut lines: Vec<&str> = Vec::new();
+    for token in tokens.iter().rev().skip_while(|t| t.start >= pos) {
+        let gap = &code[token.end()..end];
+        let line_start = code[..token.start].rfind('\n').map_or(0, |i| i + 1);
+        if token.kind != TokenKind::Comment || !gap.trim().is_empty() || gap.matches('\n').count() > 1
+            || !code[line_start..token.start].trim().is_empty() {
+            break;
+        }
+        lines.splice(0..0, token.text.lines());
+        end = token.start;
+    }
+
+    let text: Vec<&str> = lines.iter()
+        .map(|line| {
+            let line = line.trim();
+            let line = line.strip_prefix("///").or_else(|| line.strip_prefix("//")).unwrap_or(line);
+            let line = line.strip_prefix("/**").or_else(|| line.strip_prefix("/*")).unwrap_or(line);
+            let line = line.strip_suffix("*/").unwrap_or(line).trim();
+            line.strip_prefix('*').unwrap_or(line).trim()
+        })
+        .collect();
+    let text = text.join("\n").trim().to_string();
+    if text.is_empty() { None } else { Some(text) }
+}
+
+// Splits a comment into its description and @param descriptions, e.g.
+//     Returns true if the file exists
+//     @param filename path relative to fs_game
+fn parse_doc_comment(comment: &str) -> (Option<String>, BTreeMap<String, String>)
+{
+    let mut description: Vec<&str> = Vec::new();
+    let mut params: BTreeMap<String, String> = BTreeMap::new();
+    let mut last_param: Option<String> = None;
+    for line in comment.lines() {
+        if let Some(rest) = line.strip_prefix("@param") {
+            // Doxygen direction, e.g. @param[in]
+            let rest = rest.strip_prefix('[').and_then(|r| r.split_once(']')).map_or(rest, |(_, r)| r).trim();
+            let mut parts = rest.splitn(2, char::is_whitespace);
+            if let Some(name) = parts.next().filter(|n| !n.is_empty()) {
+                params.insert(name.to_string(), parts.next().unwrap_or("").trim().to_string());
+                last_param = Some(name.to_string());
             }
-        }).collect();
+        } else if let Some(rest) = line.strip_prefix("@brief") {
+            description.push(rest.trim());
+            last_param = None;
+        } else if line.starts_with('@') {
+            last_param = None;
+        } else if let Some(name) = &last_param && !line.is_empty() {
+            // Continuation of a multiline @param
+            let text = params.entry(name.clone()).or_default();
+            text.push(' ');
+            text.push_str(line);
+        } else {
+            description.push(line);
+            last_param = None;
+        }
+    }
+    let description = description.join("\n").trim().to_string();
+    (if description.is_empty() { None } else { Some(description) }, params)
+}
+
+// Parameters and returns of a handler, body being its own code with the helpers it calls inlined
+pub(crate) fn analyze_handler(body: &str, comment: Option<&str>, types: &TypeTables) -> ScriptFunctionDetails
+{
+    let mut params = extract_params(body, types);
+    let mut overloads = extract_overloads(body, types);
+    let returns = returns::infer(body, types);
+    let errors = extract_errors(body);
+    let receiver = classify_receiver(body);
+
+    let (description, param_descriptions) = comment.map(parse_doc_comment).unwrap_or_default();
+    for param in params.iter_mut().flatten().chain(overloads.iter_mut().flatten()) {
+        param.description = param_descriptions.get(&param.param_name).cloned();
+    }
+
+    ScriptFunctionDetails {
+        params,
+        overloads,
+        returns: returns.types,
+        return_conditions: returns.conditions,
+        errors,
+        description,
+        receiver,
+    }
+}
+
+static PARAMS_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\s*stackGetParams\(\s*"([^"]+)"\s*,([^)]+)\)"#).unwrap()
+});
+
+// Every stackGetParams("...", ...) call in the body, in order
+fn stack_get_params(body: &str, types: &TypeTables) -> Vec<Vec<ScriptParameter>>
+{
+    PARAMS_RE.captures_iter(body).map(|caps| {
+        let param_types: Vec<String> = caps[1].chars().map(|s| types.letter(s)).collect();
         let param_names: Vec<String> = caps[2]
             .split(',')
             .map(|s| s.trim().trim_start_matches('&').to_string())
             .collect();
-        
-        let params: Vec<ScriptParameter> = param_types.into_iter()
-            .zip(param_names.into_iter())
+
+        param_types.into_iter()
+            .zip(param_names)
             .map(|(param_type, param_name)| {
-                ScriptParameter { param_type, param_name }
+                ScriptParameter { param_type, param_name, ..Default::default() }
             })
+            .collect()
+    }).collect()
+}
+
+// Signatures of the other stackGetParams branches, e.g.
+//     if (Scr_GetType(0) == VAR_INTEGER) stackGetParams("i", &num); else stackGetParams("s", &str);
+fn extract_overloads(body: &str, types: &TypeTables) -> Vec<Vec<ScriptParameter>>
+{
+    let mut signatures = stack_get_params(body, types);
+    if signatures.is_empty() {
+        return vec![];
+    }
+    let first = signatures.remove(0);
+    let mut overloads: Vec<Vec<ScriptParameter>> = Vec::new();
+    for signature in signatures {
+        let same = |other: &Vec<ScriptParameter>| {
+            other.len() == signature.len()
+                && other.iter().zip(&signature).all(|(a, b)| a.param_type == b.param_type && a.param_name == b.param_name)
+        };
+        if !same(&first) && !overloads.iter().any(same) {
+            overloads.push(signature);
+        }
+    }
+    overloads
+}
+
+// What the scr_entref_t of a method refers to, going by the code that checks or uses it.
+// The first match wins, a hud element method may also look up the player it belongs to
+static RECEIVERS: LazyLock<Vec<(&str, Regex)>> = LazyLock::new(|| {
+    [
+        ("hud element", r"(?i)hudelem"),
+        ("player", r"MAX_CLIENTS|SV_GameClientNum|svs\.clients|\bg?client_t\b|playerState_t|not a player"),
+        ("entity", r"g_entities|SV_GentityNum|\bgentity_t\b|CLASS_NUM_ENTITY"),
+    ].into_iter().map(|(receiver, pattern)| (receiver, Regex::new(pattern).unwrap())).collect()
+});
+
+fn classify_receiver(body: &str) -> Option<String>
+{
+    RECEIVERS.iter()
+        .find(|(_, re)| re.is_match(body))
+        .map(|(receiver, _)| receiver.to_string())
+}
+
+// ^1 and friends
+static COLOR_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\^\d").unwrap());
+
+// Literal messages passed to stackError, Scr_Error and warnings/errors printed with Com_Printf
+fn extract_errors(body: &str) -> Vec<String>
+{
+    let tokens: Vec<Token> = cpp_lexer::tokenize(body)
+        .into_iter()
+        .filter(|t| t.kind != TokenKind::Comment)
+        .collect();
+
+    let mut errors: Vec<String> = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if !(token.is("stackError") || token.is("Scr_Error") || token.is("Com_Printf")) || !tokens.get(i + 1).is_some_and(|t| t.is("(")) {
+            continue;
+        }
+        // Adjacent literals are one string: "a" "b"
+        let message: String = tokens[i + 2..].iter()
+            .take_while(|t| t.kind == TokenKind::Str)
+            .map(|t| t.str_value())
             .collect();
-        Some(params)
-    } else {
-        Some(vec![ ScriptParameter {
-            param_type: "unknown".to_string(),
-            param_name: "unknown".to_string()
-        } ].into())
+        let message = COLOR_RE.replace_all(&message, "");
+        let message = message.trim_end_matches("\\n").trim();
+        if message.is_empty() {
+            continue;
+        }
+        if token.is("Com_Printf") && !(message.to_lowercase().contains("warning") || message.to_lowercase().contains("error")) {
+            continue;
+        }
+        if !errors.iter().any(|e| e == message) {
+            errors.push(message.to_string());
+        }
     }
+    errors
 }
 
-fn extract_return_types(body: &str) -> io::Result<Vec<String>>
+// Extract parameter types from stackGetParams and Scr_Get*/stackGetParam* accessors
+fn extract_params(body: &str, types: &TypeTables) -> Option<Vec<ScriptParameter>>
 {
-    let re = Regex::new(r"Scr_Add(\w+)\s*\(").unwrap();
-    let mut return_types = HashSet::new();
+    let mut params: Vec<ScriptParameter> = stack_get_params(body, types).into_iter().next().unwrap_or_default();
 
-    for cap in re.captures_iter(body) {
-        let func_name = format!("Scr_Add{}", &cap[1]);
-        if let Some(return_type) = map_scr_add_to_type(&func_name) {
-            return_types.insert(return_type);
+    // Arguments read one at a time, e.g. int id = Scr_GetInt(0); or stackGetParamInt(1, &flag);
+    // Reads behind a Scr_GetNumParam check are optional
+    let arity = arity::analyze(body);
+    let mut indexed: BTreeMap<usize, ScriptParameter> = BTreeMap::new();
+    let mut variadic: Option<(usize, ScriptParameter)> = None;
+    for access in find_accessors(body) {
+        let param_type = types.accessor(&access.suffix);
+        if let Ok(index) = access.index.parse::<usize>() {
+            if index < params.len() {
+                continue;
+            }
+            let optional = arity.is_guarded(access.pos, index);
+            let param_name = access.var.unwrap_or_else(|| format!("arg{}", index));
+            match indexed.get_mut(&index) {
+                // Scr_GetType(0) followed by Scr_GetInt(0) and the like
+                Some(existing) if existing.param_type == "unknown" => {
+                    existing.param_type = param_type;
+                    existing.param_name = param_name;
+                }
+                Some(existing) => {
+                    if !optional {
+                        existing.optional = false;
+                        existing.default = None;
+                    }
+                }
+                None => {
+                    let default = if optional { arity::default_value(body, &param_name) } else { None };
+                    indexed.insert(index, ScriptParameter {
+                        param_type,
+                        param_name,
+                        optional,
+                        default,
+                        ..Default::default()
+                    });
+                }
+            }
+        } else if let Some(argument_loop) = arity.loops.iter().find(|l| l.range.contains(&access.pos) && l.counter == access.index) {
+            variadic.get_or_insert((argument_loop.first_index, ScriptParameter {
+                param_type,
+                param_name: "...".to_string(),
+                optional: true,
+                variadic: true,
+                ..Default::default()
+            }));
         }
     }
+    // A loop over the arguments that reads them some other way
+    if variadic.is_none() && let Some(argument_loop) = arity.loops.first() {
+        variadic = Some((argument_loop.first_index, ScriptParameter {
+            param_type: "unknown".to_string(),
+            param_name: "...".to_string(),
+            optional: true,
+            variadic: true,
+            ..Default::default()
+        }));
+    }
 
-    if return_types.is_empty() {
-        Ok(vec!["unknown".to_string()])
-    } else {
-        Ok(return_types.into_iter().collect())
+    for (index, param) in indexed {
+        if variadic.as_ref().is_some_and(|(first, _)| index >= *first) {
+            break;
+        }
+        while params.len() < index {
+            params.push(ScriptParameter {
+                param_type: "unknown".to_string(),
+                param_name: format!("arg{}", params.len()),
+                optional: param.optional,
+                ..Default::default()
+            });
+        }
+        params.push(param);
     }
+    if let Some((_, param)) = variadic {
+        params.push(param);
+    }
+
+    if params.is_empty() {
+        params.push(ScriptParameter {
+            param_type: "unknown".to_string(),
+            param_name: "unknown".to_string(),
+            ..Default::default()
+        });
+    }
+    Some(params)
 }
 
-fn map_scr_add_to_type(func_name: &str) -> Option<String>
+// A single argument read, e.g. Scr_GetInt(0) or stackGetParamString(i, &str)
+struct Accessor {
+    pos: usize,
+    suffix: String,         // Int, String, ...
+    index: String,          // a number or a loop counter
+    var: Option<String>,    // variable the value ends up in
+}
+
+// stackGetParamInt(1, &flag)
+static STACK_ACCESSOR_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"stackGetParam(\w+)\s*\(\s*(\w+)\s*,\s*&?\s*(\w+)").unwrap()
+});
+// flag = (int)Scr_GetInt(1)
+static SCR_ACCESSOR_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:\b(\w+)\s*=\s*(?:\(\s*[\w\s\*]+\)\s*)?)?\bScr_Get(\w+)\s*\(\s*(\w+)\s*\)").unwrap()
+});
+
+fn find_accessors(body: &str) -> Vec<Accessor>
 {
-    match func_name {
-        "Scr_AddBool" => Some("bool".to_string()),
-        "Scr_AddInt" => Some("int".to_string()),
-        "Scr_AddFloat" => Some("float".to_string()),
-        "Scr_AddString" => Some("string".to_string()),
-        "Scr_AddArray" => Some("array".to_string()),
-        "Scr_AddVector" => Some("vector".to_string()),
-        "Scr_AddObject" => Some("object".to_string()),
-        //"Scr_AddUndefined" => Some("undefined (on failure)".to_string()),
-        _ => None,//Some("unknown".to_string()),
+    let mut accessors: Vec<Accessor> = STACK_ACCESSOR_RE.captures_iter(body)
+        .map(|c| Accessor {
+            pos: c.get(0).unwrap().start(),
+            suffix: c[1].to_string(),
+            index: c[2].to_string(),
+            var: Some(c[3].to_string()),
+        })
+        .collect();
+    accessors.extend(SCR_ACCESSOR_RE.captures_iter(body).map(|c| Accessor {
+        pos: c.get(2).unwrap().start(),
+        suffix: c[2].to_string(),
+        index: c[3].to_string(),
+        var: c.get(1).map(|v| v.as_str().to_string()),
+    }));
+    accessors.sort_by_key(|a| a.pos);
+    accessors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CACHE_FILE;
+    use std::fs;
+
+    // Registry read from code written to a temporary gsc.cpp, name keeps parallel tests apart
+    fn parse_registry(name: &str, code: &str) -> Result<Registry>
+    {
+        let registry = std::env::temp_dir().join(format!("ScriptDocTool-{}-{}.cpp", std::process::id(), name));
+        fs::write(&registry, code).unwrap();
+        let layout = RegistryLayout {
+            registry: registry.clone(),
+            src_dir: std::env::temp_dir(),
+            function_array: "scriptFunctions".to_string(),
+            method_array: "scriptMethods".to_string(),
+        };
+        let result = parse_gsc_cpp(&layout, &std::env::temp_dir());
+        fs::remove_file(&registry).unwrap();
+        result
+    }
+
+    fn script_names(functions: &[ScriptFunction]) -> Vec<&str>
+    {
+        functions.iter().map(|f| f.script_name.as_str()).collect()
+    }
+
+    #[test]
+    fn entries_split_over_lines()
+    {
+        let registry = parse_registry("split", r#"
+scriptFunction_t scriptFunctions[] = {
+    {
+        "file_exists",
+        gsc_utils_file_exists,
+        0
+    },
+    {"sqrt",gsc_utils_sqrt ,0 }
+};
+scriptMethod_t scriptMethods[] = {
+    {"getip", gsc_player_getip, 0}
+};
+"#).unwrap();
+        assert_eq!(script_names(&registry.functions), ["file_exists", "sqrt"]);
+        assert_eq!(registry.functions[0].name, "gsc_utils_file_exists");
+        assert_eq!(registry.functions[0].registered_at.as_ref().map(|at| at.line), Some(3));
+        assert_eq!(registry.functions[1].name, "gsc_utils_sqrt");
+        assert_eq!(script_names(&registry.methods), ["getip"]);
+    }
+
+    #[test]
+    fn cast_handlers_are_read_as_the_last_identifier()
+    {
+        let registry = parse_registry("cast", r#"
+scriptFunction_t scriptFunctions[] = {
+    {"opt", (xfunction_t)gsc_utils_opt, 0},
+    {"ref", &gsc_utils_ref, 0},
+    {NULL, NULL, 0}
+};
+"#).unwrap();
+        let handlers: Vec<&str> = registry.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(handlers, ["gsc_utils_opt", "gsc_utils_ref"]);
+    }
+
+    #[test]
+    fn missing_registry_array_is_an_error()
+    {
+        let result = parse_registry("empty", "int x;\n");
+        assert!(matches!(result, Err(Error::Parse { line: None, .. })));
+    }
+
+    #[test]
+    fn commented_and_guarded_entries()
+    {
+        let registry = parse_registry("guards", r#"
+scriptFunction_t scriptFunctions[] = {
+    //{"line_comment", gsc_line_comment, 0},
+    /*
+    {"block_comment", gsc_block_comment, 0},
+    */
+#if COMPILE_SQLITE == 1
+    {"sqlite", gsc_sqlite, 0},
+#elif defined(USE_MYSQL)
+    {"mysql", gsc_mysql, 0},
+#else
+    {"nodb", gsc_nodb, 0},
+#endif
+#ifndef DEBUG
+    #if 0
+    {"never", gsc_never, 0},
+    #endif
+    {"release", gsc_release, 0},
+#endif // DEBUG
+    {"always", gsc_always, 0},
+    {NULL, NULL, 0}
+};
+"#).unwrap();
+        let conditions: Vec<(&str, Option<&str>)> = registry.functions.iter()
+            .map(|f| (f.script_name.as_str(), f.condition.as_deref()))
+            .collect();
+        assert_eq!(conditions, [
+            ("sqlite", Some("COMPILE_SQLITE")),
+            ("mysql", Some("!COMPILE_SQLITE && USE_MYSQL")),
+            ("nodb", Some("!COMPILE_SQLITE && !USE_MYSQL")),
+            ("release", Some("!DEBUG")),
+            ("always", None),
+        ]);
+    }
+
+    #[test]
+    fn compound_conditions_are_normalized_per_part()
+    {
+        assert_eq!(normalize_condition("defined(A) && B == 0"), "A && !B");
+        assert_eq!(normalize_condition("!(A && B)"), "!(A && B)");
+        assert_eq!(normalize_condition("defined(A) || defined(B)"), "defined(A) || defined(B)");
+        assert_eq!(negate_condition("!A"), "A");
+        assert_eq!(negate_condition("A > 2"), "!(A > 2)");
+    }
+
+    fn signature(params: &[ScriptParameter]) -> Vec<String>
+    {
+        params.iter()
+            .map(|p| {
+                let mut text = format!("{} {}", p.param_type, p.param_name);
+                if let Some(default) = &p.default {
+                    text.push_str(&format!(" = {}", default));
+                }
+                if p.optional && !p.variadic {
+                    text = format!("[{}]", text);
+                }
+                text
+            })
+            .collect()
+    }
+
+    fn params_of(body: &str) -> Vec<String>
+    {
+        let types = TypeTables::preset("t1x").unwrap();
+        signature(&analyze_handler(body, None, &types).params.unwrap())
+    }
+
+    #[test]
+    fn arguments_behind_the_argument_count_are_optional()
+    {
+        let params = params_of(r#"
+    char *path = Scr_GetString(0);
+    int mode = 0;
+    if (Scr_GetNumParam() > 1)
+        mode = Scr_GetInt(1);
+    Scr_AddInt(mode);
+"#);
+        assert_eq!(params, ["string path", "[int mode = 0]"]);
+    }
+
+    #[test]
+    fn arguments_read_in_a_loop_are_variadic()
+    {
+        let params = params_of(r#"
+    char *format = Scr_GetString(0);
+    for (int i = 1; i < Scr_GetNumParam(); i++)
+        values[i] = Scr_GetString(i);
+"#);
+        assert_eq!(params, ["string format", "string ..."]);
+    }
+
+    #[test]
+    fn accessors_with_their_index_and_variable()
+    {
+        let accessors = find_accessors("int id = Scr_GetInt(0);\nstackGetParamString(1, &name);\nflag = (qboolean)Scr_GetInt(2);\nuse(Scr_GetEntity(4));");
+        let found: Vec<(&str, &str, Option<&str>)> = accessors.iter()
+            .map(|a| (a.suffix.as_str(), a.index.as_str(), a.var.as_deref()))
+            .collect();
+        assert_eq!(found, [
+            ("Int", "0", Some("id")),
+            ("String", "1", Some("name")),
+            ("Int", "2", Some("flag")),
+            ("Entity", "4", None),
+        ]);
+    }
+
+    #[test]
+    fn parameters_from_accessors()
+    {
+        let params = params_of("int id = Scr_GetInt(0);\ngentity_t *ent = Scr_GetEntity(2);");
+        assert_eq!(params, ["int id", "unknown arg1", "entity ent"]);
+        // stackGetParams gives the first arguments, accessors the ones after
+        let params = params_of("stackGetParams(\"s\", &path);\nstackGetParamInt(1, &flags);");
+        assert_eq!(params, ["string path", "int flags"]);
+        assert_eq!(params_of("return;"), ["unknown unknown"]);
+    }
+
+    #[test]
+    fn other_stack_get_params_branches_are_overloads()
+    {
+        let types = TypeTables::preset("t1x").unwrap();
+        let overloads = extract_overloads(r#"
+    if (Scr_GetType(0) == VAR_INTEGER)
+        stackGetParams("i", &num);
+    else if (Scr_GetNumParam() == 2)
+        stackGetParams("sv", &name, &origin);
+    else
+        stackGetParams("i", &num);
+"#, &types);
+        let overloads: Vec<Vec<String>> = overloads.iter().map(|o| signature(o)).collect();
+        assert_eq!(overloads, [["string name", "vector origin"]]);
+        assert!(extract_overloads("stackGetParams(\"i\", &num);", &types).is_empty());
+    }
+
+    #[test]
+    fn malformed_entries_are_skipped()
+    {
+        let registry = parse_registry("malformed", r#"
+scriptFunction_t scriptFunctions[] = {
+    {"good", gsc_good, 0},
+    {"bad"},
+    {NULL, NULL, 0}
+};
+"#).unwrap();
+        assert_eq!(script_names(&registry.functions), ["good"]);
+        assert_eq!(registry.skipped.len(), 1);
+        assert!(registry.skipped[0].contains(":4: malformed registry entry {\"bad\"}"));
+    }
+
+    #[test]
+    fn literal_error_messages()
+    {
+        let errors = extract_errors(r#"
+    if (!stackGetParams("s", &path)) {
+        stackError("gsc_utils_file_exists() argument is undefined or has a wrong type");
+        return;
+    }
+    if (len > 255)
+        Scr_Error("^1path " "too long\n");
+    Com_Printf("^3Warning: file %s not found\n", path);
+    Com_Printf("opened %s\n", path);
+    stackError(message);
+    stackError("gsc_utils_file_exists() argument is undefined or has a wrong type");
+"#);
+        assert_eq!(errors, [
+            "gsc_utils_file_exists() argument is undefined or has a wrong type",
+            "path too long",
+            "Warning: file %s not found",
+        ]);
+    }
+
+    #[test]
+    fn comments_above_definitions_describe_them()
+    {
+        let source = parse_source_file(r#"
+void gsc_utils_nosql() { Scr_AddInt(1); } // not about the next handler
+void gsc_utils_first() { Scr_AddInt(2); }
+#endif // COMPILE_X
+void gsc_utils_second() { Scr_AddInt(3); }
+// Separated by a blank line
+
+/**
+ * Checks whether a file exists.
+ */
+static void gsc_utils_file_exists() { Scr_AddBool(1); }
+"#);
+        let comments: Vec<(&str, Option<&str>)> = source.functions.iter()
+            .map(|f| (f.name.as_str(), f.comment.as_deref()))
+            .collect();
+        assert_eq!(comments, [
+            ("gsc_utils_nosql", None),
+            ("gsc_utils_first", None),
+            ("gsc_utils_second", None),
+            ("gsc_utils_file_exists", Some("Checks whether a file exists.")),
+        ]);
+    }
+
+    #[test]
+    fn param_tags_describe_arguments()
+    {
+        let types = TypeTables::preset("t1x").unwrap();
+        let comment = "Prints a formatted message.\n@param[in] format text with %s\nplaceholders\n@return nothing";
+        let details = analyze_handler("stackGetParams(\"s\", &format);", Some(comment), &types);
+        assert_eq!(details.description.as_deref(), Some("Prints a formatted message."));
+        let params = details.params.unwrap();
+        assert_eq!(params[0].description.as_deref(), Some("text with %s placeholders"));
+    }
+
+    #[test]
+    fn method_receivers()
+    {
+        assert_eq!(classify_receiver("if (ref.entnum >= MAX_CLIENTS) { stackError(\"not a player\"); }").as_deref(), Some("player"));
+        assert_eq!(classify_receiver("gentity_t *ent = &g_entities[ref.entnum];").as_deref(), Some("entity"));
+        // A hud element method that also looks up its owner
+        assert_eq!(classify_receiver("game_hudelem_t *hud = &g_hudelems[ref.entnum]; client_t *cl;").as_deref(), Some("hud element"));
+        assert_eq!(classify_receiver("Scr_AddInt(ref.entnum);"), None);
+    }
+
+    #[test]
+    fn developer_flags()
+    {
+        let registry = parse_registry("developer", r#"
+scriptFunction_t scriptFunctions[] = {
+    {"a", gsc_a, 0},
+    {"b", gsc_b, 1},
+    {"c", gsc_c, qfalse},
+    {"d", gsc_d, qtrue},
+    {"e", gsc_e},
+};
+"#).unwrap();
+        let flags: Vec<bool> = registry.functions.iter().map(|f| f.developer).collect();
+        assert_eq!(flags, [false, true, false, true, false]);
+    }
+
+    #[test]
+    fn diagnostics_are_returned_on_the_result()
+    {
+        let root = std::env::temp_dir().join(format!("ScriptDocTool-{}-diagnostics", std::process::id()));
+        fs::create_dir_all(root.join("src/gsc")).unwrap();
+        fs::write(root.join("src/gsc/gsc.cpp"), r#"
+scriptFunction_t scriptFunctions[] = {
+    {"sqrt", gsc_utils_sqrt, 0},
+    {"ghost", gsc_utils_ghost, 0},
+    {"testfoo", gsc_test_foo, 0},
+};
+"#).unwrap();
+        fs::write(root.join("src/gsc/gsc_utils.cpp"), "void gsc_utils_sqrt() { Scr_AddFloat(0); }\nvoid gsc_utils_hidden() {}\n").unwrap();
+
+        let mut options = ParseOptions::new(&root, &Config::default(), "t1x").unwrap();
+        options.explain = vec!["ghost".to_string(), "gsc_utils_hidden".to_string()];
+        let layout = RegistryLayout {
+            registry: PathBuf::from("src/gsc/gsc.cpp"),
+            src_dir: PathBuf::from("src/gsc"),
+            function_array: "scriptFunctions".to_string(),
+            method_array: "scriptMethods".to_string(),
+        };
+        let result = parse(&layout, &options).unwrap();
+        let cached = root.join(CACHE_FILE).exists();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(!cached);
+        assert_eq!(result.undefined, [("ghost".to_string(), "gsc_utils_ghost".to_string())]);
+        assert_eq!(result.unregistered.len(), 1);
+        assert_eq!(result.unregistered[0].0, "gsc_utils_hidden");
+        assert_eq!(result.excluded.get("exclude handler:gsc_test*"), Some(&vec!["testfoo".to_string()]));
+        assert!(result.explain["ghost"].iter().any(|step| step.starts_with("no definition of gsc_utils_ghost")));
+        assert_eq!(result.explain["gsc_utils_hidden"][0], "not registered in gsc.cpp as a script name or handler");
     }
 }
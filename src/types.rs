@@ -0,0 +1,214 @@
+// Tables turning stackGetParams letters, accessors and Scr_Add* calls into script types
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::cache::fnv1a;
+use crate::config::Config;
+use crate::error::{self, Error, Result};
+
+#[derive(Debug, Clone)]
+pub struct TypeTables {
+    letters: HashMap<char, String>,     // stackGetParams("si", ...)
+    accessors: HashMap<String, String>, // Scr_GetInt, stackGetParamInt -> "Int"
+    returns: HashMap<String, String>,   // Scr_AddInt
+    display: HashMap<String, String>,   // type names as written in the docs
+    make_array: Vec<String>,            // Scr_MakeArray, pushes an empty array
+    add_to_array: Vec<String>,          // Scr_AddArray, moves the last value into the array below it
+}
+
+fn table(entries: &[(&str, &str)]) -> HashMap<String, String>
+{
+    entries.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+}
+
+impl TypeTables {
+    pub fn preset(name: &str) -> Result<TypeTables>
+    {
+        let mut tables = TypeTables {
+            letters: [
+                ('i', "int"),
+                ('v', "vector"),
+                ('f', "float"),
+                ('s', "string"),
+                ('c', "const string"),
+                ('l', "localized string"),
+            ].iter().map(|(c, ty)| (*c, ty.to_string())).collect(),
+            accessors: table(&[
+                ("Int", "int"),
+                ("Float", "float"),
+                ("String", "string"),
+                ("ConstString", "const string"),
+                ("LocalizedString", "localized string"),
+                ("IString", "localized string"),
+                ("Vector", "vector"),
+                ("Bool", "bool"),
+                ("Entity", "entity"),
+                ("Function", "function"),
+                ("Object", "object"),
+            ]),
+            returns: table(&[
+                ("Scr_AddBool", "bool"),
+                ("Scr_AddInt", "int"),
+                ("Scr_AddFloat", "float"),
+                ("Scr_AddString", "string"),
+                ("Scr_AddIString", "localized string"),
+                ("Scr_AddVector", "vector"),
+                ("Scr_AddObject", "object"),
+                ("Scr_AddEntity", "entity"),
+                ("Scr_AddUndefined", "undefined"),
+            ]),
+            display: HashMap::new(),
+            make_array: vec!["Scr_MakeArray".to_string()],
+            add_to_array: vec!["Scr_AddArray".to_string(), "Scr_AddArrayStringIndexed".to_string()],
+        };
+        match name {
+            // iw1x shares the stack API of t1x, project specific additions go in the config
+            "t1x" | "iw1x" => {}
+            // libcod wraps the Scr_Add* calls in stackPush* macros
+            "libcod" => {
+                let pushes: Vec<(String, String)> = tables.returns.iter()
+                    .filter_map(|(func, ty)| Some((format!("stackPush{}", func.strip_prefix("Scr_Add")?), ty.clone())))
+                    .collect();
+                tables.returns.extend(pushes);
+                tables.make_array.push("stackPushArray".to_string());
+                tables.add_to_array.push("stackPushArrayLast".to_string());
+            }
+            _ => {
+                return Err(Error::Config {
+                    file: None,
+                    line: None,
+                    message: format!("Unknown type preset '{}', expected t1x, iw1x or libcod", name),
+                });
+            }
+        }
+        Ok(tables)
+    }
+
+    // The preset with the project's additions and overrides on top
+    pub fn from_config(config: &Config, default_preset: &str) -> Result<TypeTables>
+    {
+        let origin = &config.origin;
+        let config = &config.types;
+        let preset = config.preset.as_deref().unwrap_or(default_preset);
+        let mut tables = TypeTables::preset(preset).map_err(|e| origin.locate(preset, e))?;
+        let letters = error::collect(config.letters.iter().map(|(letter, ty)| {
+            let mut chars = letter.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok((c, ty.clone())),
+                _ => Err(Error::Config {
+                    file: None,
+                    line: None,
+                    message: format!("stackGetParams letter '{}' must be a single character", letter),
+                }).map_err(|e| origin.locate(letter, e)),
+            }
+        }))?;
+        tables.letters.extend(letters);
+        tables.accessors.extend(config.accessors.clone());
+        tables.returns.extend(config.returns.clone());
+        tables.display.extend(config.display.clone());
+        Ok(tables)
+    }
+
+    // Changes whenever any table does, for the cache
+    pub fn fingerprint(&self) -> u64
+    {
+        let letters: BTreeMap<String, &String> = self.letters.iter().map(|(c, ty)| (c.to_string(), ty)).collect();
+        let tables = format!(
+            "{:?}{:?}{:?}{:?}{:?}{:?}",
+            letters,
+            self.accessors.iter().collect::<BTreeMap<_, _>>(),
+            self.returns.iter().collect::<BTreeMap<_, _>>(),
+            self.display.iter().collect::<BTreeMap<_, _>>(),
+            self.make_array,
+            self.add_to_array,
+        );
+        fnv1a(&[tables.as_bytes()])
+    }
+
+    pub fn letter(&self, letter: char) -> String
+    {
+        self.letters.get(&letter).cloned().unwrap_or_else(|| "unknown".to_string())
+    }
+
+    // Int -> int, ConstString -> const string, Type -> unknown
+    pub fn accessor(&self, suffix: &str) -> String
+    {
+        self.accessors.get(suffix).cloned().unwrap_or_else(|| "unknown".to_string())
+    }
+
+    pub fn scr_add(&self, func_name: &str) -> Option<String>
+    {
+        self.returns.get(func_name).cloned()
+    }
+
+    pub fn is_make_array(&self, func_name: &str) -> bool
+    {
+        self.make_array.iter().any(|f| f == func_name)
+    }
+
+    pub fn is_add_to_array(&self, func_name: &str) -> bool
+    {
+        self.add_to_array.iter().any(|f| f == func_name)
+    }
+
+    // Name to print in the docs, "array of X" renames the element types too
+    pub fn display(&self, ty: &str) -> String
+    {
+        if let Some(name) = self.display.get(ty) {
+            return name.clone();
+        }
+        match ty.strip_prefix("array of ") {
+            Some(elements) => format!("array of {}", elements.split(" or ").map(|e| self.display(e)).collect::<Vec<_>>().join(" or ")),
+            None => ty.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tables(config: &str) -> Result<TypeTables>
+    {
+        let config = Config { types: toml::from_str(config).unwrap(), ..Default::default() };
+        TypeTables::from_config(&config, "t1x")
+    }
+
+    #[test]
+    fn config_extends_and_overrides_the_preset()
+    {
+        let types = tables(r#"
+letters = { e = "entity", i = "integer" }
+accessors = { Hud = "hud element" }
+returns = { Scr_AddEntityNum = "entity" }
+"#).unwrap();
+        assert_eq!(types.letter('e'), "entity");
+        assert_eq!(types.letter('i'), "integer");
+        assert_eq!(types.letter('s'), "string");
+        assert_eq!(types.letter('x'), "unknown");
+        assert_eq!(types.accessor("Hud"), "hud element");
+        assert_eq!(types.scr_add("Scr_AddEntityNum").as_deref(), Some("entity"));
+        assert_ne!(types.fingerprint(), TypeTables::preset("t1x").unwrap().fingerprint());
+    }
+
+    #[test]
+    fn display_names_apply_to_array_elements()
+    {
+        let types = tables("display = { string = \"str\", \"localized string\" = \"istring\" }").unwrap();
+        assert_eq!(types.display("string"), "str");
+        assert_eq!(types.display("int"), "int");
+        assert_eq!(types.display("array of int or string"), "array of int or str");
+        assert_eq!(types.display("array of localized string"), "array of istring");
+    }
+
+    #[test]
+    fn presets()
+    {
+        let libcod = TypeTables::preset("libcod").unwrap();
+        assert_eq!(libcod.scr_add("stackPushInt").as_deref(), Some("int"));
+        assert!(libcod.is_make_array("stackPushArray"));
+        assert!(TypeTables::preset("t1x").unwrap().scr_add("stackPushInt").is_none());
+        assert!(matches!(TypeTables::preset("iw2x"), Err(Error::Config { .. })));
+        assert!(matches!(tables("letters = { ab = \"int\" }"), Err(Error::Config { .. })));
+    }
+}